@@ -1,3 +1,15 @@
+pub mod classifier;
+pub mod compare;
+pub mod flakes;
+pub mod labelling;
+pub mod rules;
+
+use crate::error::Error;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Labels {
     Transient = 1,
     Environment = 2,
@@ -7,7 +19,7 @@ pub enum Labels {
 }
 
 impl Labels {
-    pub fn to_str(&mut self) -> String {
+    pub fn to_str(&self) -> String {
         match self {
             Self::Transient => "💨 Transient".to_string(),
             Self::Environment => "🤖 Environment".to_string(),
@@ -18,5 +30,45 @@ impl Labels {
     }
 }
 
+impl FromStr for Labels {
+    type Err = Error;
+
+    /// Parses a label from its name, ignoring case, emojis and surrounding whitespace
+    fn from_str(label: &str) -> Result<Self, Self::Err> {
+        let name = label
+            .trim_start_matches(|c: char| !c.is_ascii_alphabetic())
+            .trim()
+            .to_lowercase();
+        match name.as_str() {
+            "transient" => Ok(Self::Transient),
+            "environment" => Ok(Self::Environment),
+            "code" => Ok(Self::Code),
+            "dependency" => Ok(Self::Dependency),
+            "unknown" => Ok(Self::Unknown),
+            _ => Err(Error::InvalidInput(format!(
+                "{} is not a valid label, use transient, environment, code, dependency or unknown",
+                label
+            ))),
+        }
+    }
+}
+
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Checks that labels are parsed from their names as well as from their display strings
+    fn test_labels_from_str() {
+        assert_eq!(
+            "environment".parse::<Labels>().unwrap(),
+            Labels::Environment
+        );
+        assert_eq!(
+            "🤖 Environment".parse::<Labels>().unwrap(),
+            Labels::Environment
+        );
+        assert_eq!(" Code ".parse::<Labels>().unwrap(), Labels::Code);
+        assert!("flaky".parse::<Labels>().is_err());
+    }
+}
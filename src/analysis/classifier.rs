@@ -0,0 +1,213 @@
+use crate::analysis::Labels;
+use crate::error::Result;
+use crate::identification::{normalize_event, Event};
+use crate::system::{check_slash, create_dir, read_json, write_json};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Token statistics of all training builds with the same label
+pub struct LabelStatistics {
+    /// Number of training builds with the label
+    pub builds: usize,
+    /// Total number of tokens in the events of those builds
+    pub tokens: usize,
+    /// Number of occurrences of every token in the events of those builds
+    pub counts: HashMap<String, usize>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Multinomial naive Bayes model predicting the label of a build from the tokens of its events
+pub struct Classifier {
+    pub labels: BTreeMap<Labels, LabelStatistics>,
+    /// Number of distinct tokens over all labels
+    pub vocabulary: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Label predicted by the classifier
+pub struct Prediction {
+    pub label: Labels,
+    /// Probability of the predicted label, between 0 and 1
+    pub confidence: f64,
+}
+
+/// Placeholders of the variable parts masked by `normalize_event`
+const MASKS: [&str; 5] = ["<TIME>", "<UUID>", "<IP>", "<HEX>", "<NUM>"];
+
+/// Splits the events of a build into lowercase word tokens, leaving out masked variable parts
+fn tokenize(events: &[Event]) -> Vec<String> {
+    let mut tokens = Vec::new();
+    for event in events {
+        let normalized = MASKS
+            .iter()
+            .fold(normalize_event(&event.content), |content, mask| {
+                content.replace(mask, " ")
+            })
+            .to_lowercase();
+        for token in normalized.split(|c: char| !c.is_alphanumeric()) {
+            if token.len() > 1 {
+                tokens.push(token.to_string());
+            }
+        }
+    }
+    tokens
+}
+
+impl Classifier {
+    /// Trains a new model from labelled builds
+    ///
+    /// # Arguments
+    ///
+    /// * `builds` - The label and the identified events of every training build
+    ///
+    /// # Returns
+    ///
+    /// The trained `Classifier`
+    pub fn train(builds: &[(Labels, Vec<Event>)]) -> Self {
+        let mut classifier = Classifier::default();
+        let mut vocabulary: HashSet<String> = HashSet::new();
+        for (label, events) in builds {
+            let statistics = classifier.labels.entry(*label).or_default();
+            statistics.builds += 1;
+            for token in tokenize(events) {
+                statistics.tokens += 1;
+                *statistics.counts.entry(token.clone()).or_default() += 1;
+                vocabulary.insert(token);
+            }
+        }
+        classifier.vocabulary = vocabulary.len();
+        classifier
+    }
+
+    /// Predicts the label of a build
+    ///
+    /// # Arguments
+    ///
+    /// * `events` - The identified events of the build
+    ///
+    /// # Returns
+    ///
+    /// The most probable label and its probability, or `None` if the model has not been trained
+    /// or the events contain no tokens
+    pub fn predict(&self, events: &[Event]) -> Option<Prediction> {
+        let tokens = tokenize(events);
+        let builds: usize = self
+            .labels
+            .values()
+            .map(|statistics| statistics.builds)
+            .sum();
+        if builds == 0 || tokens.is_empty() {
+            return None;
+        }
+        let scores = self
+            .labels
+            .iter()
+            .map(|(label, statistics)| {
+                let denominator = (statistics.tokens + self.vocabulary) as f64;
+                let likelihood: f64 = tokens
+                    .iter()
+                    .map(|token| {
+                        let count = statistics.counts.get(token).copied().unwrap_or(0);
+                        ((count + 1) as f64 / denominator).ln()
+                    })
+                    .sum();
+                let prior = (statistics.builds as f64 / builds as f64).ln();
+                (*label, prior + likelihood)
+            })
+            .collect::<Vec<(Labels, f64)>>();
+        let (label, best) = scores.iter().copied().max_by(|a, b| a.1.total_cmp(&b.1))?;
+        let total: f64 = scores.iter().map(|(_, score)| (score - best).exp()).sum();
+        Some(Prediction {
+            label,
+            confidence: 1.0 / total,
+        })
+    }
+
+    /// Loads the model stored in the data directory
+    ///
+    /// # Arguments
+    ///
+    /// * `data_path` - The root data directory
+    ///
+    /// # Returns
+    ///
+    /// The stored `Classifier`, or `None` if no model has been trained yet
+    pub fn load(data_path: &str) -> Result<Option<Self>> {
+        let path = Self::path(data_path);
+        if !Path::new(&path).exists() {
+            return Ok(None);
+        }
+        read_json(&path).map(Some)
+    }
+
+    /// Stores the model in the data directory, replacing a previously trained model
+    ///
+    /// # Arguments
+    ///
+    /// * `data_path` - The root data directory
+    pub fn save(&self, data_path: &str) -> Result<()> {
+        create_dir(&format!("{}analysis", check_slash(data_path)))?;
+        write_json(&Self::path(data_path), self)
+    }
+
+    fn path(data_path: &str) -> String {
+        format!("{}analysis/classifier.json", check_slash(data_path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events(lines: &[&str]) -> Vec<Event> {
+        lines
+            .iter()
+            .map(|line| Event {
+                content: line.to_string(),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    /// Checks that a build is assigned the label of the training builds with similar events
+    fn test_train_and_predict() {
+        let classifier = Classifier::train(&[
+            (
+                Labels::Environment,
+                events(&["dial tcp 10.0.0.1:443: i/o timeout", "node not ready"]),
+            ),
+            (
+                Labels::Environment,
+                events(&["dial tcp 10.0.0.7:443: connection refused"]),
+            ),
+            (
+                Labels::Code,
+                events(&["--- FAILED: TestParse", "expected 3, got 4"]),
+            ),
+        ]);
+        let prediction = classifier
+            .predict(&events(&["dial tcp 10.1.2.3:6443: i/o timeout"]))
+            .unwrap();
+        assert_eq!(prediction.label, Labels::Environment);
+        assert!(prediction.confidence > 0.5 && prediction.confidence <= 1.0);
+        let prediction = classifier
+            .predict(&events(&["--- FAILED: TestRender"]))
+            .unwrap();
+        assert_eq!(prediction.label, Labels::Code);
+        assert!(Classifier::default().predict(&events(&["error"])).is_none());
+    }
+
+    #[test]
+    /// Checks that masked variable parts are left out, but words spelled like the masks are kept
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize(&events(&[
+                "context deadline exceeded after 30s: time limit"
+            ])),
+            vec!["context", "deadline", "exceeded", "after", "time", "limit"]
+        );
+    }
+}
@@ -0,0 +1,257 @@
+use crate::collection::prow::{get_build_info, BuildInfo};
+use crate::collection::{split_build_ref, BuildState};
+use crate::config::Config;
+use crate::identification::normalize_event;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+/// Summary of a single build taking part in a comparison
+pub struct ComparedBuild {
+    pub build_id: String,
+    /// Prow instance the build was collected from
+    pub instance: Option<String>,
+    pub state: Option<String>,
+    pub job_type: Option<String>,
+    /// Number of distinct normalised events found for this build
+    pub event_count: usize,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+/// Differences between the identified events of several builds
+pub struct BuildComparison {
+    pub builds: Vec<ComparedBuild>,
+    /// Events found in every compared build
+    pub shared: Vec<String>,
+    /// Events found in only one build, keyed by build reference, i.e. `<instance>/<build_id>`
+    pub unique: BTreeMap<String, Vec<String>>,
+    /// Events found in at least one failed build but in none of the successful ones
+    pub failure_only: Vec<String>,
+    /// Events found in at least one successful build but in none of the failed ones
+    pub success_only: Vec<String>,
+    pub error: Option<String>,
+}
+
+/// Compares the identified events of several builds
+///
+/// # Arguments
+///
+/// * `build_ids` - The build IDs to compare, optionally prefixed with their instance as in `<instance>/<build_id>`
+/// * `source_path` - Root path to where the build data is stored
+/// * `config` - The Arcalog configuration
+///
+/// # Returns
+///
+/// `BuildComparison` - Struct containing the shared and differing events
+pub async fn compare_builds(
+    build_ids: Vec<String>,
+    source_path: String,
+    config: &Config,
+) -> BuildComparison {
+    let mut unique_ids: Vec<String> = Vec::new();
+    for build_id in build_ids {
+        let build_id = build_id.trim().to_string();
+        if !build_id.is_empty() && !unique_ids.contains(&build_id) {
+            unique_ids.push(build_id);
+        }
+    }
+    if unique_ids.len() < 2 {
+        return BuildComparison {
+            error: Some("Please submit at least two different build IDs to compare.".to_string()),
+            ..Default::default()
+        };
+    }
+
+    let mut build_infos = Vec::new();
+    for reference in unique_ids {
+        let (instance, build_id) = split_build_ref(&reference);
+        let (instance, build_id) = (instance.map(str::to_string), build_id.to_string());
+        let build_info = match get_build_info(
+            build_id.clone(),
+            instance.clone(),
+            source_path.clone(),
+            config,
+        )
+        .await
+        {
+            Ok(build_info) => build_info,
+            Err(e) => BuildInfo {
+                build_id,
+                instance,
+                error: Some(e.to_string()),
+                ..Default::default()
+            },
+        };
+        build_infos.push(build_info);
+    }
+    compare_build_infos(build_infos)
+}
+
+/// Reference of a build as used in comparisons, i.e. `<instance>/<build_id>` if the instance is known
+fn build_ref(build_info: &BuildInfo) -> String {
+    match &build_info.instance {
+        Some(instance) => format!("{}/{}", instance, build_info.build_id),
+        None => build_info.build_id.clone(),
+    }
+}
+
+/// Compares the events of already loaded builds, skipping builds that could not be loaded
+///
+/// # Arguments
+///
+/// * `build_infos` - The loaded builds including their events
+///
+/// # Returns
+///
+/// `BuildComparison` - Struct containing the shared and differing events
+pub fn compare_build_infos(build_infos: Vec<BuildInfo>) -> BuildComparison {
+    let mut comparison = BuildComparison::default();
+    let mut event_sets: Vec<(String, BuildState, BTreeSet<String>)> = Vec::new();
+
+    for build_info in build_infos {
+        let reference = build_ref(&build_info);
+        let events = build_info
+            .events
+            .unwrap_or_default()
+            .iter()
+            .map(|event| normalize_event(&event.content))
+            .collect::<BTreeSet<String>>();
+        comparison.builds.push(ComparedBuild {
+            build_id: build_info.build_id.clone(),
+            instance: build_info.instance.clone(),
+            state: build_info.state.clone(),
+            job_type: build_info.job_type,
+            event_count: events.len(),
+            error: build_info.error.clone(),
+        });
+        if build_info.error.is_none() {
+            let state = build_info
+                .build_state
+                .unwrap_or_else(|| BuildState::Other(String::new()));
+            event_sets.push((reference, state, events));
+        }
+    }
+
+    if event_sets.len() < 2 {
+        comparison.error = Some(
+            "At least two of the submitted build IDs need to be valid to compare them.".to_string(),
+        );
+        return comparison;
+    }
+
+    let mut occurrences: BTreeMap<&String, Vec<usize>> = BTreeMap::new();
+    for (index, (_, _, events)) in event_sets.iter().enumerate() {
+        for event in events {
+            occurrences.entry(event).or_default().push(index);
+        }
+    }
+
+    // Builds that neither failed nor succeeded, e.g. aborted or running ones, are left out of the split
+    let is_failed = |index: &usize| event_sets[*index].1.is_failed();
+    let is_success = |index: &usize| event_sets[*index].1 == BuildState::Success;
+    let has_failures = (0..event_sets.len()).any(|index| is_failed(&index));
+    let has_successes = (0..event_sets.len()).any(|index| is_success(&index));
+    for (reference, _, _) in &event_sets {
+        comparison.unique.insert(reference.clone(), Vec::new());
+    }
+
+    for (event, builds) in occurrences {
+        if builds.len() == event_sets.len() {
+            comparison.shared.push(event.clone());
+        } else if builds.len() == 1 {
+            let reference = &event_sets[builds[0]].0;
+            comparison
+                .unique
+                .get_mut(reference)
+                .unwrap()
+                .push(event.clone());
+        }
+        if has_failures && has_successes {
+            let in_failure = builds.iter().any(is_failed);
+            let in_success = builds.iter().any(is_success);
+            if in_failure && !in_success {
+                comparison.failure_only.push(event.clone());
+            } else if in_success && !in_failure {
+                comparison.success_only.push(event.clone());
+            }
+        }
+    }
+    comparison
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identification::Event;
+
+    fn build(build_id: &str, state: BuildState, events: Vec<&str>) -> BuildInfo {
+        BuildInfo {
+            build_id: build_id.to_string(),
+            state: Some(state.as_str().to_string()),
+            build_state: Some(state),
+            events: Some(
+                events
+                    .iter()
+                    .map(|event| Event {
+                        content: event.to_string(),
+                        ..Default::default()
+                    })
+                    .collect(),
+            ),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    /// Checks that events are split into shared, unique, failure-only and success-only sets
+    fn test_compare_build_infos() {
+        let comparison = compare_build_infos(vec![
+            build(
+                "1",
+                BuildState::Failure,
+                vec!["error: a", "error: timeout after 30s"],
+            ),
+            build(
+                "2",
+                BuildState::Failure,
+                vec!["error: a", "error: timeout after 45s"],
+            ),
+            build("3", BuildState::Success, vec!["error: a", "warning: b"]),
+            build(
+                "4",
+                BuildState::Error,
+                vec!["error: a", "error: timeout after 60s"],
+            ),
+        ]);
+        assert!(comparison.error.is_none());
+        assert_eq!(comparison.shared, vec!["error: a"]);
+        assert_eq!(comparison.unique["3"], vec!["warning: b"]);
+        assert!(comparison.unique["1"].is_empty());
+        assert_eq!(comparison.failure_only, vec!["error: timeout after <NUM>s"]);
+        assert_eq!(comparison.success_only, vec!["warning: b"]);
+    }
+
+    #[test]
+    /// Checks that aborted and running builds count neither as failed nor as successful
+    fn test_compare_unfinished_builds() {
+        let comparison = compare_build_infos(vec![
+            build("1", BuildState::Failure, vec!["error: a", "error: b"]),
+            build("2", BuildState::Aborted, vec!["error: a"]),
+            build("3", BuildState::Pending, vec!["warning: c"]),
+        ]);
+        assert!(comparison.error.is_none());
+        assert!(comparison.failure_only.is_empty());
+        assert!(comparison.success_only.is_empty());
+
+        let comparison = compare_build_infos(vec![
+            build("1", BuildState::Failure, vec!["error: a", "error: b"]),
+            build("2", BuildState::Aborted, vec!["error: a"]),
+            build("3", BuildState::Pending, vec!["warning: c"]),
+            build("4", BuildState::Success, vec!["warning: c"]),
+        ]);
+        // `error: a` of the aborted build does not count as seen in a successful build
+        assert_eq!(comparison.failure_only, vec!["error: a", "error: b"]);
+        assert_eq!(comparison.success_only, vec!["warning: c"]);
+    }
+}
@@ -0,0 +1,372 @@
+use crate::collection::prow::open_index;
+use crate::collection::{BuildRecord, BuildState};
+use crate::error::Result;
+use crate::identification::junit::{collect_test_results, TestStatus, TestSuite};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Settings of the flaky job detection
+pub struct FlakesConfig {
+    /// Number of days before now whose builds are taken into account
+    #[serde(default = "FlakesConfig::default_window_days")]
+    pub window_days: u32,
+}
+
+impl FlakesConfig {
+    fn default_window_days() -> u32 {
+        14
+    }
+}
+
+impl Default for FlakesConfig {
+    fn default() -> Self {
+        FlakesConfig {
+            window_days: Self::default_window_days(),
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Builds of a job that both passed and failed on the same commit
+pub struct FlakyCommit {
+    pub commit: String,
+    pub successes: usize,
+    pub failures: usize,
+    pub builds: Vec<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// A test that both passed and failed on identical code
+pub struct FlakyTest {
+    /// Name of the test case including its class name
+    pub name: String,
+    /// Number of commits on which the test both passed and failed
+    pub commits: usize,
+    /// Number of times the test passed on these commits, counting retries within a build
+    pub passes: usize,
+    /// Number of times the test failed on these commits, counting retries within a build
+    pub failures: usize,
+    /// Builds in which the test failed on these commits
+    pub builds: Vec<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// A job that both passed and failed on identical code, or that ran tests which did
+pub struct FlakyJob {
+    pub job: String,
+    /// Prow instance the job runs on, as jobs of different instances may share their name
+    pub instance: String,
+    /// Number of builds of the job in the window
+    pub builds: usize,
+    /// Number of distinct commits the job ran on in the window
+    pub commits: usize,
+    pub flaky_commits: Vec<FlakyCommit>,
+    /// Share of commits on which the job both passed and failed, between 0 and 1
+    pub flakiness: f64,
+    /// Tests of the job that both passed and failed on the same commit, the flakiest first
+    pub flaky_tests: Vec<FlakyTest>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Flaky jobs found in the collected history
+pub struct FlakeReport {
+    pub window_days: u32,
+    /// Jobs that are flaky or ran flaky tests, the flakiest first
+    pub jobs: Vec<FlakyJob>,
+    pub error: Option<String>,
+}
+
+/// Identifies the code a build ran against by its base commit and the heads of all its pull
+/// requests, e.g. `base+head` for a presubmit, so that batches and rebased pull requests do not
+/// share a group with other code
+fn commit(build: &BuildRecord) -> Option<String> {
+    let refs = build.refs.as_ref()?;
+    let mut shas = refs
+        .get("base_sha")
+        .and_then(|sha| sha.as_str())
+        .filter(|sha| !sha.is_empty())
+        .into_iter()
+        .collect::<Vec<&str>>();
+    let pulls = refs.get("pulls").and_then(|pulls| pulls.as_array());
+    for pull in pulls.into_iter().flatten() {
+        // Without the head of every pull request, the code of the build is unknown
+        let sha = pull
+            .get("sha")
+            .and_then(|sha| sha.as_str())
+            .filter(|sha| !sha.is_empty())?;
+        shas.push(sha);
+    }
+    (!shas.is_empty()).then(|| shas.join("+"))
+}
+
+#[derive(Default)]
+/// Builds of a job on a single commit and the results of their tests
+struct CommitRuns {
+    commit: FlakyCommit,
+    /// Passes, failures and failing builds of every test case by name
+    tests: BTreeMap<String, (usize, usize, Vec<String>)>,
+}
+
+/// Groups builds by instance, job and commit and reports jobs and tests that both passed and
+/// failed on a commit
+///
+/// Builds without a known commit or with a state other than success or failure are ignored.
+///
+/// # Arguments
+///
+/// * `builds` - The builds to examine
+/// * `test_results` - Test suites of the builds by instance and build ID, where builds without
+///   results are left out
+///
+/// # Returns
+///
+/// The jobs that are flaky or ran flaky tests, ordered by flakiness, the flakiest first
+pub fn find_flakes(
+    builds: &[BuildRecord],
+    test_results: &HashMap<(String, String), Vec<TestSuite>>,
+) -> Vec<FlakyJob> {
+    let mut jobs: BTreeMap<(&str, &str), BTreeMap<String, CommitRuns>> = BTreeMap::new();
+    for build in builds {
+        let Some(commit) = commit(build) else {
+            continue;
+        };
+        if build.state != BuildState::Success && build.state != BuildState::Failure {
+            continue;
+        }
+        let runs = jobs
+            .entry((&build.instance, &build.job))
+            .or_default()
+            .entry(commit.clone())
+            .or_insert_with(|| CommitRuns {
+                commit: FlakyCommit {
+                    commit,
+                    ..Default::default()
+                },
+                ..Default::default()
+            });
+        if build.state == BuildState::Success {
+            runs.commit.successes += 1;
+        } else {
+            runs.commit.failures += 1;
+        }
+        runs.commit.builds.push(build.build_id.clone());
+        let suites = test_results
+            .get(&(build.instance.clone(), build.build_id.clone()))
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        for case in suites.iter().flat_map(|suite| &suite.cases) {
+            let test = runs.tests.entry(case.full_name()).or_default();
+            match case.status {
+                TestStatus::Passed => test.0 += 1,
+                TestStatus::Failed | TestStatus::Error => {
+                    test.1 += 1;
+                    if !test.2.contains(&build.build_id) {
+                        test.2.push(build.build_id.clone());
+                    }
+                }
+                TestStatus::Skipped => {}
+            }
+        }
+    }
+
+    let mut flaky_jobs = Vec::new();
+    for ((instance, job), commits) in jobs {
+        let builds = commits.values().map(|runs| runs.commit.builds.len()).sum();
+        let commit_count = commits.len();
+        let mut flaky_tests: BTreeMap<String, FlakyTest> = BTreeMap::new();
+        let mut flaky_commits = Vec::new();
+        for runs in commits.into_values() {
+            for (name, (passes, failures, failed_builds)) in runs.tests {
+                if passes == 0 || failures == 0 {
+                    continue;
+                }
+                let test = flaky_tests
+                    .entry(name.clone())
+                    .or_insert_with(|| FlakyTest {
+                        name,
+                        ..Default::default()
+                    });
+                test.commits += 1;
+                test.passes += passes;
+                test.failures += failures;
+                test.builds.extend(failed_builds);
+            }
+            if runs.commit.successes > 0 && runs.commit.failures > 0 {
+                flaky_commits.push(runs.commit);
+            }
+        }
+        if flaky_commits.is_empty() && flaky_tests.is_empty() {
+            continue;
+        }
+        let mut flaky_tests = flaky_tests.into_values().collect::<Vec<FlakyTest>>();
+        flaky_tests.sort_by_key(|test| std::cmp::Reverse(test.commits));
+        flaky_jobs.push(FlakyJob {
+            job: job.to_string(),
+            instance: instance.to_string(),
+            builds,
+            commits: commit_count,
+            flakiness: flaky_commits.len() as f64 / commit_count as f64,
+            flaky_commits,
+            flaky_tests,
+        });
+    }
+    flaky_jobs.sort_by(|a, b| b.flakiness.total_cmp(&a.flakiness));
+    flaky_jobs
+}
+
+/// Reports the flaky jobs among all indexed builds started within the window
+///
+/// # Arguments
+///
+/// * `data_path` - The root data directory
+/// * `window_days` - Number of days before now whose builds are taken into account
+///
+/// # Returns
+///
+/// `FlakeReport` containing the flaky jobs
+pub async fn flake_report(data_path: &str, window_days: u32) -> Result<FlakeReport> {
+    let since = chrono::Utc::now() - chrono::Duration::days(window_days as i64);
+    let since = since.format("%Y-%m-%dT%H:%M:%SZ").to_string();
+    let builds = open_index(data_path)
+        .await?
+        .list(Some(&since), &[BuildState::Success, BuildState::Failure])?;
+    // Tests can only be flaky on commits that a job ran on more than once
+    let mut runs: HashMap<(&str, &str, String), usize> = HashMap::new();
+    for build in &builds {
+        if let Some(commit) = commit(build) {
+            *runs
+                .entry((&build.instance, &build.job, commit))
+                .or_default() += 1;
+        }
+    }
+    let mut test_results = HashMap::new();
+    for build in &builds {
+        let Some(commit) = commit(build) else {
+            continue;
+        };
+        if runs[&(build.instance.as_str(), build.job.as_str(), commit)] < 2 {
+            continue;
+        }
+        match collect_test_results(build, data_path).await {
+            Ok(suites) if !suites.is_empty() => {
+                test_results.insert((build.instance.clone(), build.build_id.clone()), suites);
+            }
+            Ok(_) => {}
+            Err(e) => println!(
+                "Skipping the test results of build {}/{}: {}",
+                build.instance, build.build_id, e
+            ),
+        }
+    }
+    Ok(FlakeReport {
+        window_days,
+        jobs: find_flakes(&builds, &test_results),
+        error: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(build_id: &str, job: &str, state: &str, refs: serde_json::Value) -> BuildRecord {
+        BuildRecord {
+            build_id: build_id.to_string(),
+            job: job.to_string(),
+            state: BuildState::from(state),
+            refs: Some(refs),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    /// Checks that only jobs with mixed results on the same code are reported
+    fn test_find_flakes() {
+        let pull = serde_json::json!({"base_sha": "base", "pulls": [{"sha": "head"}]});
+        let base = serde_json::json!({"base_sha": "base"});
+        let rebased = serde_json::json!({"base_sha": "base2", "pulls": [{"sha": "head"}]});
+        let batch =
+            serde_json::json!({"base_sha": "base", "pulls": [{"sha": "head"}, {"sha": "other"}]});
+        let flakes = find_flakes(
+            &[
+                build("1", "e2e", "failure", pull.clone()),
+                build("2", "e2e", "success", pull.clone()),
+                build("3", "e2e", "failure", base.clone()),
+                build("4", "unit", "failure", pull.clone()),
+                build("5", "unit", "success", base.clone()),
+                build("6", "unit", "success", rebased),
+                build("7", "unit", "success", batch),
+                BuildRecord {
+                    instance: "internal".to_string(),
+                    ..build("8", "e2e", "success", base)
+                },
+            ],
+            &HashMap::new(),
+        );
+        assert_eq!(flakes.len(), 1);
+        assert_eq!(flakes[0].job, "e2e");
+        assert_eq!(flakes[0].builds, 3);
+        assert_eq!(flakes[0].commits, 2);
+        assert_eq!(flakes[0].flakiness, 0.5);
+        assert_eq!(flakes[0].flaky_commits[0].commit, "base+head");
+        assert_eq!(flakes[0].flaky_commits[0].builds, vec!["1", "2"]);
+        assert!(flakes[0].flaky_tests.is_empty());
+    }
+
+    #[test]
+    /// Checks that tests which both passed and failed on the same code are reported, even if the
+    /// job itself always failed
+    fn test_find_flaky_tests() {
+        use crate::identification::junit::TestCase;
+        let refs = serde_json::json!({"base_sha": "base"});
+        let suite = |cases: Vec<(&str, TestStatus)>| {
+            vec![TestSuite {
+                cases: cases
+                    .into_iter()
+                    .map(|(name, status)| TestCase {
+                        name: name.to_string(),
+                        status,
+                        ..Default::default()
+                    })
+                    .collect(),
+                ..Default::default()
+            }]
+        };
+        let test_results = HashMap::from([
+            (
+                (String::new(), "1".to_string()),
+                suite(vec![
+                    ("dns", TestStatus::Failed),
+                    ("storage", TestStatus::Failed),
+                ]),
+            ),
+            (
+                (String::new(), "2".to_string()),
+                suite(vec![
+                    ("dns", TestStatus::Passed),
+                    ("storage", TestStatus::Failed),
+                ]),
+            ),
+        ]);
+        let flakes = find_flakes(
+            &[
+                build("1", "e2e", "failure", refs.clone()),
+                build("2", "e2e", "failure", refs),
+            ],
+            &test_results,
+        );
+        assert_eq!(flakes.len(), 1);
+        assert!(flakes[0].flaky_commits.is_empty());
+        assert_eq!(
+            flakes[0].flaky_tests,
+            vec![FlakyTest {
+                name: "dns".to_string(),
+                commits: 1,
+                passes: 1,
+                failures: 1,
+                builds: vec!["1".to_string()],
+            }]
+        );
+    }
+}
@@ -0,0 +1,229 @@
+use crate::analysis::classifier::Classifier;
+use crate::analysis::Labels;
+use crate::collection::check_instance;
+use crate::collection::prow::find_build;
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::identification::collect_events;
+use crate::index::BuildIndex;
+use crate::system::{check_slash, create_dir, files_in_folder, read_json, write_json};
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+use std::path::Path;
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Root cause of a build as recorded by an engineer
+pub struct HumanLabel {
+    pub build_id: String,
+    /// Prow instance the build was collected from, found from the build ID if left empty
+    #[serde(default)]
+    pub instance: String,
+    pub label: Labels,
+    /// Who recorded the label
+    pub author: Option<String>,
+    /// When the label was recorded, in RFC 3339 format
+    pub timestamp: String,
+    /// Free-text explanation of the root cause
+    pub note: Option<String>,
+    /// URL of the issue tracking the root cause
+    pub issue: Option<String>,
+}
+
+fn labels_path(data_path: &str) -> String {
+    format!("{}labels", check_slash(data_path))
+}
+
+/// Checks that a build ID can safely be used as a file name
+fn check_build_id(build_id: &str) -> Result<()> {
+    if build_id.is_empty()
+        || !build_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(Error::InvalidInput(format!(
+            "{} is not a valid build ID",
+            build_id
+        )));
+    }
+    Ok(())
+}
+
+fn label_path(data_path: &str, instance: &str, build_id: &str) -> String {
+    format!("{}/{}/{}.json", labels_path(data_path), instance, build_id)
+}
+
+/// Records the root cause of a collected build, replacing a previously recorded label
+///
+/// # Arguments
+///
+/// * `data_path` - The root data directory
+/// * `label` - The label to store, with `timestamp` left empty to use the current time
+///
+/// # Returns
+///
+/// The stored label, or `Error::NotFound` if the build has not been collected
+pub async fn save_label(data_path: &str, mut label: HumanLabel) -> Result<HumanLabel> {
+    check_build_id(&label.build_id)?;
+    let instance = match label.instance.is_empty() {
+        true => None,
+        false => {
+            check_instance(&label.instance)?;
+            Some(label.instance.as_str())
+        }
+    };
+    let Some(build) = find_build(&label.build_id, instance, data_path).await? else {
+        return Err(Error::NotFound(format!(
+            "Build {} has not been collected yet",
+            label.build_id
+        )));
+    };
+    label.instance = build.instance;
+    if label.timestamp.is_empty() {
+        label.timestamp = chrono::Utc::now().to_rfc3339();
+    }
+    create_dir(&format!("{}/{}", labels_path(data_path), label.instance))?;
+    write_json(
+        &label_path(data_path, &label.instance, &label.build_id),
+        &label,
+    )?;
+    Ok(label)
+}
+
+/// Loads the label recorded for a build
+///
+/// # Arguments
+///
+/// * `data_path` - The root data directory
+/// * `instance` - The Prow instance the build was collected from
+/// * `build_id` - The requested build ID
+///
+/// # Returns
+///
+/// The recorded label, or `None` if the build has not been labelled
+pub fn load_label(data_path: &str, instance: &str, build_id: &str) -> Result<Option<HumanLabel>> {
+    check_instance(instance)?;
+    check_build_id(build_id)?;
+    let path = label_path(data_path, instance, build_id);
+    if !Path::new(&path).exists() {
+        return Ok(None);
+    }
+    read_json(&path).map(Some)
+}
+
+/// Loads all recorded labels
+///
+/// # Arguments
+///
+/// * `data_path` - The root data directory
+///
+/// # Returns
+///
+/// All recorded labels ordered by instance and build ID
+pub async fn list_labels(data_path: &str) -> Result<Vec<HumanLabel>> {
+    let path = labels_path(data_path);
+    if !Path::new(&path).exists() {
+        return Ok(Vec::new());
+    }
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(&path).map_err(|e| Error::io(&path, e))? {
+        let entry = entry.map_err(|e| Error::io(&path, e))?;
+        if entry.path().is_dir() {
+            files.append(&mut files_in_folder(&entry.path().to_string_lossy()).await?);
+        }
+    }
+    files.retain(|file| file.ends_with(".json"));
+    files.sort();
+    files.iter().map(|file| read_json(file)).collect()
+}
+
+/// Moves labels recorded before builds were separated by Prow instance into the folder of their instance
+///
+/// Labels of builds that are not in the index or have been collected from several instances are
+/// left in place.
+///
+/// # Arguments
+///
+/// * `index` - The build index
+/// * `data_path` - The root data directory
+pub fn move_legacy_labels(index: &BuildIndex, data_path: &str) -> Result<()> {
+    let path = labels_path(data_path);
+    if !Path::new(&path).exists() {
+        return Ok(());
+    }
+    for entry in std::fs::read_dir(&path).map_err(|e| Error::io(&path, e))? {
+        let file = entry.map_err(|e| Error::io(&path, e))?.path();
+        if !file.is_file() || file.extension().is_none_or(|extension| extension != "json") {
+            continue;
+        }
+        let file = file.to_string_lossy().to_string();
+        let mut label: HumanLabel = read_json(&file)?;
+        let builds = index.find(&label.build_id)?;
+        let [build] = builds.as_slice() else {
+            println!(
+                "⛔\t\x1b[93m\x1b[1mKeeping the label of build {} in {}, as its instance is unknown\x1b[0m",
+                label.build_id, path
+            );
+            continue;
+        };
+        label.instance = build.instance.clone();
+        create_dir(&format!("{}/{}", path, label.instance))?;
+        write_json(
+            &label_path(data_path, &label.instance, &label.build_id),
+            &label,
+        )?;
+        std::fs::remove_file(&file).map_err(|e| Error::io(&file, e))?;
+    }
+    Ok(())
+}
+
+/// Trains the classifier on all labelled builds and stores it in the data directory
+///
+/// Labelled builds whose events cannot be collected are skipped.
+///
+/// # Arguments
+///
+/// * `data_path` - The root data directory
+/// * `config` - The Arcalog configuration
+///
+/// # Returns
+///
+/// The trained `Classifier`
+pub async fn train_from_labels(data_path: &str, config: &Config) -> Result<Classifier> {
+    let mut builds = Vec::new();
+    for label in list_labels(data_path).await? {
+        // Labels recorded by earlier versions that could not be moved have no instance
+        let instance = Some(label.instance.as_str()).filter(|instance| !instance.is_empty());
+        let build = match find_build(&label.build_id, instance, data_path).await {
+            Ok(Some(build)) => build,
+            Ok(None) => {
+                println!("Skipping {}: build is not in the index", label.build_id);
+                continue;
+            }
+            Err(e) => {
+                println!("Skipping {}: {}", label.build_id, e);
+                continue;
+            }
+        };
+        match collect_events(&build, data_path, &config.identification).await {
+            Ok(events) => builds.push((label.label, events)),
+            Err(e) => println!("Skipping {}: {}", label.build_id, e),
+        }
+    }
+    let classifier = Classifier::train(&builds);
+    classifier.save(data_path)?;
+    Ok(classifier)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Checks that build IDs that could escape the labels folder are rejected
+    fn test_check_build_id() {
+        assert!(check_build_id("1729384756102938475").is_ok());
+        assert!(check_build_id("").is_err());
+        assert!(check_build_id("../index").is_err());
+    }
+}
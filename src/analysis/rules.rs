@@ -0,0 +1,246 @@
+use crate::analysis::flakes::FlakesConfig;
+use crate::analysis::Labels;
+use crate::collection::BuildState;
+use crate::error::{Error, Result};
+use crate::identification::Event;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Rule assigning a label to builds whose job or events match all of its conditions
+pub struct RuleConfig {
+    pub name: String,
+    pub label: Labels,
+    /// Rules with a higher priority win over rules with a lower priority
+    #[serde(default)]
+    pub priority: i64,
+    /// Why builds matching the rule get the label
+    #[serde(default)]
+    pub explanation: String,
+    /// Regular expression that has to match the whole job name
+    pub job: Option<String>,
+    /// States of which the build has to be in one, e.g. `error` for infrastructure failures
+    #[serde(default)]
+    pub states: Vec<BuildState>,
+    /// Regular expression that has to match the content of an event
+    pub pattern: Option<String>,
+    /// Regular expression that has to match the artifact path of an event
+    pub path: Option<String>,
+    /// Corpus terms that all have to be found in an event
+    #[serde(default)]
+    pub terms: Vec<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// `analysis` section of the configuration file
+pub struct AnalysisConfig {
+    /// Rules used to label failed builds
+    #[serde(default)]
+    pub rules: Vec<RuleConfig>,
+    /// Settings of the flaky job detection
+    #[serde(default)]
+    pub flakes: FlakesConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// A rule that matched a build
+pub struct FiredRule {
+    pub name: String,
+    pub label: Labels,
+    pub priority: i64,
+    pub explanation: String,
+    /// Number of events matching the event conditions of the rule
+    pub events: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Result of classifying a build with the configured rules
+pub struct Classification {
+    /// Label of the fired rule with the highest priority, or `Unknown` if no rule fired
+    pub label: Labels,
+    /// All rules that fired, the winning rule first
+    pub rules: Vec<FiredRule>,
+}
+
+struct Rule {
+    config: RuleConfig,
+    job: Option<Regex>,
+    pattern: Option<Regex>,
+    path: Option<Regex>,
+}
+
+impl Rule {
+    fn matches_event(&self, event: &Event) -> bool {
+        self.pattern
+            .as_ref()
+            .is_none_or(|pattern| pattern.is_match(&event.content))
+            && self
+                .path
+                .as_ref()
+                .is_none_or(|path| path.is_match(&event.path))
+            && self.config.terms.iter().all(|term| {
+                event
+                    .terms
+                    .iter()
+                    .any(|found| found.eq_ignore_ascii_case(term))
+            })
+    }
+
+    fn has_event_conditions(&self) -> bool {
+        self.pattern.is_some() || self.path.is_some() || !self.config.terms.is_empty()
+    }
+}
+
+/// Compiled classification rules
+pub struct Rules {
+    rules: Vec<Rule>,
+}
+
+impl AnalysisConfig {
+    /// Compiles the configured rules
+    ///
+    /// # Returns
+    ///
+    /// The compiled `Rules`, or a parse error naming the rule with an invalid regular expression
+    pub fn rules(&self) -> Result<Rules> {
+        let compile = |rule: &RuleConfig, pattern: &Option<String>, anchored: bool| {
+            pattern
+                .as_ref()
+                .map(|pattern| {
+                    let pattern = if anchored {
+                        format!("^(?:{})$", pattern)
+                    } else {
+                        pattern.clone()
+                    };
+                    Regex::new(&pattern)
+                        .map_err(|e| Error::parse(&format!("analysis rule {}", rule.name), e))
+                })
+                .transpose()
+        };
+        let mut rules = Vec::new();
+        for rule in &self.rules {
+            rules.push(Rule {
+                job: compile(rule, &rule.job, true)?,
+                pattern: compile(rule, &rule.pattern, false)?,
+                path: compile(rule, &rule.path, false)?,
+                config: rule.clone(),
+            });
+        }
+        Ok(Rules { rules })
+    }
+}
+
+impl Rules {
+    /// Classifies a build by its job name, state and events
+    ///
+    /// A rule fires if its job pattern and states match and, if it has any event conditions, at
+    /// least one event matches all of them. Of equally prioritised rules, the one configured first wins.
+    ///
+    /// # Arguments
+    ///
+    /// * `job` - Name of the job the build belongs to
+    /// * `state` - State of the build
+    /// * `events` - The identified events of the build
+    ///
+    /// # Returns
+    ///
+    /// `Classification` containing the winning label and all fired rules
+    pub fn classify(&self, job: &str, state: &BuildState, events: &[Event]) -> Classification {
+        let mut fired: Vec<FiredRule> = Vec::new();
+        for rule in &self.rules {
+            if !rule
+                .job
+                .as_ref()
+                .is_none_or(|pattern| pattern.is_match(job))
+                || !(rule.config.states.is_empty() || rule.config.states.contains(state))
+            {
+                continue;
+            }
+            let matching = events
+                .iter()
+                .filter(|event| rule.matches_event(event))
+                .count();
+            if rule.has_event_conditions() && matching == 0 {
+                continue;
+            }
+            fired.push(FiredRule {
+                name: rule.config.name.clone(),
+                label: rule.config.label,
+                priority: rule.config.priority,
+                explanation: rule.config.explanation.clone(),
+                events: matching,
+            });
+        }
+        // The sort is stable, so the configuration order decides between equal priorities
+        fired.sort_by_key(|rule| std::cmp::Reverse(rule.priority));
+        Classification {
+            label: fired.first().map_or(Labels::Unknown, |rule| rule.label),
+            rules: fired,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Checks that the fired rule with the highest priority decides the label
+    fn test_classify() {
+        let config: AnalysisConfig = serde_yaml::from_str(
+            "
+rules:
+  - name: dns
+    label: environment
+    priority: 10
+    explanation: Name resolution failed
+    pattern: \"no such host\"
+  - name: unit-tests
+    label: code
+    job: \"pull-.*-unit\"
+    terms: [failed]
+  - name: registry
+    label: dependency
+    priority: 20
+    path: \"^image-pull/\"
+  - name: infrastructure
+    label: environment
+    priority: 5
+    states: [error]
+",
+        )
+        .unwrap();
+        let rules = config.rules().unwrap();
+        let events = vec![
+            Event {
+                path: "build-log.txt".to_string(),
+                content: "dial tcp: lookup api: no such host".to_string(),
+                ..Default::default()
+            },
+            Event {
+                path: "build-log.txt".to_string(),
+                content: "--- FAILED: TestParse".to_string(),
+                terms: vec!["failed".to_string()],
+                ..Default::default()
+            },
+        ];
+
+        let classification = rules.classify("pull-arcalog-unit", &BuildState::Failure, &events);
+        assert_eq!(classification.label, Labels::Environment);
+        let fired = classification
+            .rules
+            .iter()
+            .map(|rule| (rule.name.as_str(), rule.events))
+            .collect::<Vec<(&str, usize)>>();
+        assert_eq!(fired, vec![("dns", 1), ("unit-tests", 1)]);
+
+        let classification = rules.classify("periodic-e2e", &BuildState::Failure, &events[1..]);
+        assert_eq!(classification.label, Labels::Unknown);
+        assert!(classification.rules.is_empty());
+
+        let classification = rules.classify("periodic-e2e", &BuildState::Error, &[]);
+        assert_eq!(classification.label, Labels::Environment);
+    }
+}
@@ -1,4 +1,371 @@
+pub mod download;
+pub mod gcs;
+pub mod manifest;
 pub mod prow;
 
+use crate::error::{Error, Result};
+use crate::system::check_slash;
+use async_trait::async_trait;
+use download::DownloadSummary;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Stored metadata of a single build as found by a collector
+pub struct BuildRecord {
+    pub build_id: String,
+    pub url: String,
+    pub job: String,
+    pub state: BuildState,
+    pub start_time: Option<String>,
+    pub completion_time: Option<String>,
+    /// Source code references the build was run against, in the collector's own format
+    pub refs: Option<serde_json::Value>,
+    /// Name of the collector that stored the build
+    pub source: String,
+    /// Name of the collector instance the build was collected from, e.g. the host of a Prow instance
+    pub instance: String,
+    /// Run time of the build in seconds
+    pub duration: Option<i64>,
+    /// Result reported by the build itself, e.g. `SUCCESS` or `FAILURE`
+    pub result: Option<String>,
+    /// Revision of the repository the build ran against
+    pub revision: Option<String>,
+    /// Node the build ran on
+    pub node: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+/// State of a build, named after the states of Prow jobs
+pub enum BuildState {
+    /// The build has been requested but not been scheduled yet
+    #[default]
+    Triggered,
+    /// The build is waiting to be scheduled on a cluster
+    Scheduling,
+    /// The build is running
+    Pending,
+    Success,
+    Failure,
+    /// The build was stopped before it finished, e.g. because a newer build superseded it
+    Aborted,
+    /// The build could not be run due to an infrastructure problem
+    Error,
+    /// A state unknown to Arcalog, as reported by the collector
+    Other(String),
+}
+
+impl BuildState {
+    /// Name of the state as reported by Prow, e.g. `failure`
+    pub fn as_str(&self) -> &str {
+        match self {
+            BuildState::Triggered => "triggered",
+            BuildState::Scheduling => "scheduling",
+            BuildState::Pending => "pending",
+            BuildState::Success => "success",
+            BuildState::Failure => "failure",
+            BuildState::Aborted => "aborted",
+            BuildState::Error => "error",
+            BuildState::Other(state) => state,
+        }
+    }
+
+    /// Returns true if the build has finished and its artifacts are complete
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            BuildState::Success | BuildState::Failure | BuildState::Aborted | BuildState::Error
+        )
+    }
+
+    /// Returns true if the build failed, either by itself or due to an infrastructure problem
+    pub fn is_failed(&self) -> bool {
+        matches!(self, BuildState::Failure | BuildState::Error)
+    }
+}
+
+impl From<&str> for BuildState {
+    fn from(state: &str) -> Self {
+        match state.to_ascii_lowercase().as_str() {
+            "triggered" => BuildState::Triggered,
+            "scheduling" => BuildState::Scheduling,
+            "pending" => BuildState::Pending,
+            "success" => BuildState::Success,
+            "failure" => BuildState::Failure,
+            "aborted" => BuildState::Aborted,
+            "error" => BuildState::Error,
+            _ => BuildState::Other(state.to_string()),
+        }
+    }
+}
+
+impl From<String> for BuildState {
+    fn from(state: String) -> Self {
+        BuildState::from(state.as_str())
+    }
+}
+
+impl From<BuildState> for String {
+    fn from(state: BuildState) -> Self {
+        state.as_str().to_string()
+    }
+}
+
+impl std::fmt::Display for BuildState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl BuildRecord {
+    /// Path to where the artifacts of the build are stored
+    ///
+    /// # Arguments
+    ///
+    /// * `data_path` - The root data directory
+    pub fn artifact_path(&self, data_path: &str) -> String {
+        format!(
+            "{}{}/{}/artifacts/{}",
+            check_slash(data_path),
+            self.source,
+            self.instance,
+            self.build_id
+        )
+    }
+}
+
+/// Derives the name of a collector instance from its URL, e.g. `prow.k8s.io` for `https://prow.k8s.io/`
+///
+/// Characters other than letters, digits, dots, dashes and underscores are replaced, so that the
+/// name can be used as a folder name.
+///
+/// # Arguments
+///
+/// * `url` - URL of the instance or of a build collected from it
+pub fn instance_name(url: &str) -> String {
+    let host = url
+        .split_once("://")
+        .map_or(url, |(_, rest)| rest)
+        .split('/')
+        .next()
+        .unwrap_or_default();
+    host.chars()
+        .map(
+            |c| match c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_' {
+                true => c,
+                false => '_',
+            },
+        )
+        .collect()
+}
+
+/// Checks that an instance name can safely be used as a folder name
+///
+/// # Arguments
+///
+/// * `instance` - Name of the collector instance
+pub fn check_instance(instance: &str) -> Result<()> {
+    if instance.is_empty()
+        || instance == "."
+        || instance == ".."
+        // Folder in which earlier versions stored the artifacts of all instances
+        || instance == "artifacts"
+        || !instance
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_')
+    {
+        return Err(Error::InvalidInput(format!(
+            "{} is not a valid instance name",
+            instance
+        )));
+    }
+    Ok(())
+}
+
+/// Splits a build reference of the form `[<instance>/]<build_id>` into its instance and build ID
+///
+/// # Arguments
+///
+/// * `reference` - The build reference, e.g. `prow.k8s.io/1729384756102938475`
+pub fn split_build_ref(reference: &str) -> (Option<&str>, &str) {
+    match reference.trim().rsplit_once('/') {
+        Some((instance, build_id)) => (Some(instance), build_id),
+        None => (None, reference.trim()),
+    }
+}
+
+/// A log source from which Arcalog collects build metadata and artifacts
+///
+/// Implement this trait and add it to a `Registry` to collect data from additional CI or log systems.
+#[async_trait]
+pub trait Collector: Send + Sync {
+    /// Name of the collector, which is also the name of its section in the configuration file
+    fn name(&self) -> &str;
+
+    /// Discovers and stores the metadata of the latest builds
+    ///
+    /// # Arguments
+    ///
+    /// * `data_path` - The data path where the files will be stored
+    /// * `artifacts_collection` - If set to true, the artifacts of all discovered builds are downloaded as well
+    async fn download_metadata(&self, data_path: &str, artifacts_collection: bool) -> Result<()>;
+
+    /// Downloads all artifacts of the given build
+    ///
+    /// # Arguments
+    ///
+    /// * `build` - The build whose artifacts need to be downloaded
+    /// * `data_path` - The data path where the artifacts and metadata are stored
+    ///
+    /// # Returns
+    ///
+    /// `DownloadSummary` listing the files that could not be downloaded
+    async fn download_artifacts(
+        &self,
+        build: &BuildRecord,
+        data_path: &str,
+    ) -> Result<DownloadSummary>;
+
+    /// Looks up the stored metadata of the given build
+    ///
+    /// # Arguments
+    ///
+    /// * `build_id` - The requested build ID
+    /// * `instance` - The instance the build was collected from, only needed if several instances have a build with this ID
+    /// * `data_path` - The data path where the metadata is stored
+    ///
+    /// # Returns
+    ///
+    /// `BuildRecord` of the build, if it has been collected before
+    async fn find_build(
+        &self,
+        build_id: &str,
+        instance: Option<&str>,
+        data_path: &str,
+    ) -> Result<Option<BuildRecord>>;
+}
+
+/// Creates a collector from its section of the configuration file
+pub type CollectorFactory =
+    Box<dyn Fn(&serde_yaml::Value) -> Result<Box<dyn Collector>> + Send + Sync>;
+
+/// Registry of all available collectors keyed by the name of their configuration section
+pub struct Registry {
+    factories: HashMap<String, CollectorFactory>,
+}
+
+impl Default for Registry {
+    /// Creates a registry containing all collectors shipped with Arcalog
+    fn default() -> Self {
+        let mut registry = Registry::new();
+        registry.register(
+            "prow",
+            Box::new(|config| {
+                let config: prow::ProwConfig = serde_yaml::from_value(config.clone())
+                    .map_err(|e| Error::parse("prow configuration", e))?;
+                Ok(Box::new(prow::ProwCollector::new(config)?) as Box<dyn Collector>)
+            }),
+        );
+        registry
+    }
+}
+
+impl Registry {
+    /// Creates an empty registry
+    pub fn new() -> Self {
+        Registry {
+            factories: HashMap::new(),
+        }
+    }
+
+    /// Adds a collector, replacing any collector previously registered under the same name
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the configuration section the collector is created from
+    /// * `factory` - Function creating the collector from its configuration section
+    pub fn register(&mut self, name: &str, factory: CollectorFactory) {
+        self.factories.insert(name.to_string(), factory);
+    }
+
+    /// Lists the names of all registered collectors
+    pub fn names(&self) -> Vec<String> {
+        let mut names = self.factories.keys().cloned().collect::<Vec<String>>();
+        names.sort();
+        names
+    }
+
+    /// Creates the collector registered under the given name
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the collector and its configuration section
+    /// * `config` - The configuration section of the collector
+    ///
+    /// # Returns
+    ///
+    /// `None` if no collector is registered under the name, otherwise the created collector or
+    /// the reason why its configuration is invalid
+    pub fn create(
+        &self,
+        name: &str,
+        config: &serde_yaml::Value,
+    ) -> Option<Result<Box<dyn Collector>>> {
+        self.factories.get(name).map(|factory| factory(config))
+    }
+}
+
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Checks that the default registry creates the Prow collector from its configuration section
+    fn test_default_registry() {
+        let registry = Registry::default();
+        assert_eq!(registry.names(), vec!["prow"]);
+        let config: serde_yaml::Value =
+            serde_yaml::from_str("location:\n  - \"https://prow.k8s.io\"").unwrap();
+        let collector = registry.create("prow", &config).unwrap().unwrap();
+        assert_eq!(collector.name(), "prow");
+        assert!(registry.create("jenkins", &config).is_none());
+    }
+
+    #[test]
+    /// Checks that instance names are derived from URLs and build references are split
+    fn test_instance_name() {
+        assert_eq!(instance_name("https://prow.k8s.io/"), "prow.k8s.io");
+        assert_eq!(
+            instance_name("http://127.0.0.1:8080/view/1"),
+            "127.0.0.1_8080"
+        );
+        assert!(check_instance("prow.k8s.io").is_ok());
+        assert!(check_instance("artifacts").is_err());
+        assert!(check_instance("../labels").is_err());
+        assert_eq!(split_build_ref("123"), (None, "123"));
+        assert_eq!(split_build_ref("internal/123"), (Some("internal"), "123"));
+    }
+
+    #[test]
+    /// Checks that every Prow state is parsed and unknown states are kept
+    fn test_build_state() {
+        let states: Vec<BuildState> =
+            serde_json::from_str(r#"["pending", "error", "Aborted", "waiting"]"#).unwrap();
+        assert_eq!(
+            states,
+            vec![
+                BuildState::Pending,
+                BuildState::Error,
+                BuildState::Aborted,
+                BuildState::Other("waiting".to_string())
+            ]
+        );
+        assert_eq!(
+            serde_json::to_string(&states).unwrap(),
+            r#"["pending","error","aborted","waiting"]"#
+        );
+        assert!(BuildState::Error.is_failed() && BuildState::Error.is_finished());
+        assert!(!BuildState::Pending.is_finished());
+    }
+}
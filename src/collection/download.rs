@@ -0,0 +1,562 @@
+use crate::collection::gcs::GcsConfig;
+use crate::error::{Error, Result};
+use globset::{Glob, GlobBuilder, GlobMatcher, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+/// `download` section of the configuration file
+pub struct DownloadConfig {
+    /// Maximum number of requests running at the same time
+    pub concurrency: usize,
+    /// Seconds after which a request including its response body is aborted
+    pub timeout: u64,
+    /// Seconds after which connecting to a server is aborted
+    pub connect_timeout: u64,
+    /// Number of times a failed request is retried
+    pub retries: u32,
+    /// Milliseconds to wait before the first retry, doubled for every further retry
+    pub backoff: u64,
+    /// Glob patterns of the artifacts to download, relative to the artifact folder of a build,
+    /// e.g. `build-log.txt` or `**/junit*.xml`. All artifacts are downloaded if empty.
+    pub include: Vec<String>,
+    /// Glob patterns of the artifacts not to download, taking precedence over `include`
+    pub exclude: Vec<String>,
+    /// Maximum size of a single artifact in bytes
+    pub max_file_size: Option<u64>,
+    /// Maximum size of all artifacts of a build in bytes
+    pub max_build_size: Option<u64>,
+    /// Access to the Google Cloud Storage buckets artifacts are uploaded to
+    pub gcs: GcsConfig,
+}
+
+impl Default for DownloadConfig {
+    fn default() -> Self {
+        DownloadConfig {
+            concurrency: 8,
+            timeout: 120,
+            connect_timeout: 10,
+            retries: 4,
+            backoff: 500,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            max_file_size: None,
+            max_build_size: None,
+            gcs: GcsConfig::default(),
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// A file that could not be downloaded
+pub struct FailedFile {
+    pub url: String,
+    pub error: String,
+}
+
+#[skip_serializing_none]
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// A file that was not downloaded because it exceeds a size limit
+pub struct SkippedFile {
+    pub url: String,
+    /// Size in bytes, if reported by the server
+    pub size: Option<u64>,
+    pub reason: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Outcome of downloading the artifacts of a build
+pub struct DownloadSummary {
+    pub build_id: String,
+    /// Number of files that were downloaded
+    pub downloaded: usize,
+    /// Number of files that had been downloaded before
+    pub existing: usize,
+    /// Number of files that do not match the include and exclude patterns
+    pub excluded: usize,
+    /// Number of files that were corrupt or incomplete and have been downloaded again
+    pub corrupt: usize,
+    /// Number of files downloaded before that are not in the manifest and could not be checked
+    pub unverified: usize,
+    pub skipped: Vec<SkippedFile>,
+    pub failed: Vec<FailedFile>,
+}
+
+impl DownloadSummary {
+    /// Adds the counts and files of another summary of the same build
+    pub fn merge(&mut self, other: DownloadSummary) {
+        self.downloaded += other.downloaded;
+        self.existing += other.existing;
+        self.excluded += other.excluded;
+        self.corrupt += other.corrupt;
+        self.unverified += other.unverified;
+        self.skipped.extend(other.skipped);
+        self.failed.extend(other.failed);
+    }
+
+    /// Prints the files that were repaired, skipped or could not be downloaded, if any
+    pub fn report(&self) {
+        if self.corrupt > 0 {
+            println!(
+                "⛔\t\x1b[93m\x1b[1m{} corrupt or incomplete files of build {} were downloaded again\x1b[0m",
+                self.corrupt, self.build_id
+            );
+        }
+        if !self.skipped.is_empty() {
+            println!(
+                "⛔\t\x1b[93m\x1b[1m{} files of build {} were skipped:\x1b[0m",
+                self.skipped.len(),
+                self.build_id
+            );
+            for skipped in &self.skipped {
+                println!("\t{}: {}", skipped.url, skipped.reason);
+            }
+        }
+        if self.failed.is_empty() {
+            return;
+        }
+        println!(
+            "⛔\t\x1b[93m\x1b[1m{} files of build {} could not be downloaded:\x1b[0m",
+            self.failed.len(),
+            self.build_id
+        );
+        for failed in &self.failed {
+            println!("\t{}: {}", failed.url, failed.error);
+        }
+    }
+}
+
+/// Selects the artifacts to download by their path relative to the artifact folder of a build
+#[derive(Debug, Clone)]
+pub struct ArtifactFilter {
+    include: Option<GlobSet>,
+    /// Include patterns split into their folder components, `None` standing for `**`
+    include_folders: Vec<Vec<Option<GlobMatcher>>>,
+    exclude: GlobSet,
+    /// Maximum size of a single artifact in bytes
+    pub max_file_size: Option<u64>,
+    /// Maximum size of all artifacts of a build in bytes
+    pub max_build_size: Option<u64>,
+}
+
+/// Compiles a glob pattern in which `*` and `?` do not match `/`, while `**` matches any number of folders
+fn glob(pattern: &str) -> Result<Glob> {
+    GlobBuilder::new(pattern)
+        .literal_separator(true)
+        .build()
+        .map_err(|e| Error::InvalidInput(format!("Invalid artifact pattern {}: {}", pattern, e)))
+}
+
+fn glob_set(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(glob(pattern)?);
+    }
+    builder
+        .build()
+        .map_err(|e| Error::InvalidInput(format!("Invalid artifact patterns: {}", e)))
+}
+
+impl ArtifactFilter {
+    /// Compiles the include and exclude patterns of the download settings
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The `download` section of the configuration file
+    ///
+    /// # Returns
+    ///
+    /// `Error::InvalidInput` if a pattern is not a valid glob
+    pub fn new(config: &DownloadConfig) -> Result<Self> {
+        let mut include_folders = Vec::new();
+        for pattern in &config.include {
+            let mut components = Vec::new();
+            for component in pattern.split('/') {
+                components.push(match component.contains("**") {
+                    true => None,
+                    false => Some(glob(component)?.compile_matcher()),
+                });
+            }
+            include_folders.push(components);
+        }
+        Ok(ArtifactFilter {
+            include: match config.include.is_empty() {
+                true => None,
+                false => Some(glob_set(&config.include)?),
+            },
+            include_folders,
+            exclude: glob_set(&config.exclude)?,
+            max_file_size: config.max_file_size,
+            max_build_size: config.max_build_size,
+        })
+    }
+
+    /// Returns true if the artifact matches the include patterns and none of the exclude patterns
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the artifact relative to the artifact folder of the build
+    pub fn is_selected(&self, path: &str) -> bool {
+        self.include
+            .as_ref()
+            .is_none_or(|include| include.is_match(path))
+            && !self.exclude.is_match(path)
+    }
+
+    /// Returns true if no artifact within the folder can be selected, so it does not need to be listed
+    ///
+    /// A folder is skipped if an exclude pattern matches the folder itself (e.g. `**/must-gather`)
+    /// or everything within it (e.g. `**/must-gather/**`), or if no include pattern can match a
+    /// path within it.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the folder relative to the artifact folder of the build
+    pub fn is_skipped_folder(&self, path: &str) -> bool {
+        let path = path.trim_end_matches('/');
+        if self.exclude.is_match(path)
+            || (self.exclude.is_match(format!("{}/\u{1}", path))
+                && self.exclude.is_match(format!("{}/\u{1}/\u{1}", path)))
+        {
+            return true;
+        }
+        if self.include.is_none() {
+            return false;
+        }
+        let folders = path.split('/').collect::<Vec<&str>>();
+        !self.include_folders.iter().any(|components| {
+            for (i, folder) in folders.iter().enumerate() {
+                match components.get(i) {
+                    Some(None) => return true,
+                    // The last component of a pattern matches file names
+                    Some(Some(matcher)) if i + 1 < components.len() => {
+                        if !matcher.is_match(folder) {
+                            return false;
+                        }
+                    }
+                    _ => return false,
+                }
+            }
+            true
+        })
+    }
+}
+
+/// A downloaded file
+pub struct Content {
+    pub bytes: Vec<u8>,
+    /// Entity tag of the file as reported by the server
+    pub etag: Option<String>,
+}
+
+/// HTTP client shared by all collectors, limiting concurrent requests and retrying failed ones
+pub struct Downloader {
+    client: reqwest::Client,
+    config: DownloadConfig,
+    filter: ArtifactFilter,
+    permits: Semaphore,
+}
+
+static DOWNLOADER: OnceLock<Downloader> = OnceLock::new();
+
+/// Sets up the shared downloader, which is created with the default settings otherwise
+///
+/// # Arguments
+///
+/// * `config` - The `download` section of the configuration file
+///
+/// # Returns
+///
+/// `Error::InvalidInput` if the downloader has already been used or set up
+pub fn configure(config: DownloadConfig) -> Result<()> {
+    DOWNLOADER
+        .set(Downloader::new(config)?)
+        .map_err(|_| Error::InvalidInput("The downloader has already been set up".to_string()))
+}
+
+/// Returns the shared downloader
+pub fn downloader() -> &'static Downloader {
+    DOWNLOADER.get_or_init(|| {
+        Downloader::new(DownloadConfig::default()).expect("Could not create the HTTP client")
+    })
+}
+
+/// Returns true for errors that may not occur again when the request is repeated
+fn is_transient(error: &reqwest::Error) -> bool {
+    error.is_connect() || error.is_timeout() || error.is_request() || error.is_body()
+}
+
+/// Outcome of a single attempt to download something
+enum Attempt<T> {
+    Done(T),
+    /// The attempt failed, but may succeed when it is repeated after the delay in seconds
+    /// requested by the server, if any
+    Retry(Error, Option<u64>),
+    Failed(Error),
+}
+
+/// Reads the whole body of a response unless it exceeds a maximum size
+///
+/// # Returns
+///
+/// The body, a retryable error if the transfer is interrupted or the body is shorter than
+/// announced by the server, or `Error::InvalidInput` if it is larger than `limit`
+async fn read_body(
+    url: &str,
+    mut response: reqwest::Response,
+    limit: Option<u64>,
+) -> Attempt<Content> {
+    let length = response.content_length();
+    let etag = response
+        .headers()
+        .get(reqwest::header::ETAG)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string());
+    let mut bytes = Vec::new();
+    loop {
+        match response.chunk().await {
+            Ok(Some(chunk)) => {
+                bytes.extend_from_slice(&chunk);
+                if limit.is_some_and(|limit| bytes.len() as u64 > limit) {
+                    return Attempt::Failed(Error::InvalidInput(format!(
+                        "{} is larger than {} bytes",
+                        url,
+                        limit.unwrap_or_default()
+                    )));
+                }
+            }
+            Ok(None) if length.is_some_and(|length| length != bytes.len() as u64) => {
+                let message = format!(
+                    "received {} of {} bytes",
+                    bytes.len(),
+                    length.unwrap_or_default()
+                );
+                return Attempt::Retry(Error::network(url, message), None);
+            }
+            Ok(None) => return Attempt::Done(Content { bytes, etag }),
+            Err(e) => return Attempt::Retry(Error::network(url, e), None),
+        }
+    }
+}
+
+impl Downloader {
+    /// Creates a downloader with its own HTTP client
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Concurrency, timeout and retry settings
+    pub fn new(config: DownloadConfig) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout))
+            .connect_timeout(Duration::from_secs(config.connect_timeout))
+            .build()
+            .map_err(|e| Error::InvalidInput(format!("Could not create the HTTP client: {}", e)))?;
+        Ok(Downloader {
+            client,
+            filter: ArtifactFilter::new(&config)?,
+            permits: Semaphore::new(config.concurrency.max(1)),
+            config,
+        })
+    }
+
+    /// Returns the filter selecting the artifacts to download
+    pub fn filter(&self) -> &ArtifactFilter {
+        &self.filter
+    }
+
+    /// Returns the settings of the Google Cloud Storage access
+    pub fn gcs(&self) -> &GcsConfig {
+        &self.config.gcs
+    }
+
+    /// Waits before the given retry, preferring the delay requested by the server
+    async fn wait(&self, retry: u32, retry_after: Option<u64>) {
+        let backoff = self.config.backoff.saturating_mul(1 << (retry - 1).min(16));
+        let delay = retry_after.map_or(backoff, |seconds| seconds.saturating_mul(1000));
+        tokio::time::sleep(Duration::from_millis(delay.min(60_000))).await;
+    }
+
+    /// Sends a single request and checks the response status
+    ///
+    /// # Returns
+    ///
+    /// The response, or the error together with the delay requested by the server if the
+    /// request may succeed when it is repeated
+    async fn try_send(&self, method: reqwest::Method, url: &str) -> Attempt<reqwest::Response> {
+        match self.client.request(method, url).send().await {
+            Ok(response) => match response.status() {
+                reqwest::StatusCode::NOT_FOUND => {
+                    Attempt::Failed(Error::NotFound(format!("{} does not exist", url)))
+                }
+                status
+                    if status.is_server_error()
+                        || status == reqwest::StatusCode::TOO_MANY_REQUESTS =>
+                {
+                    let retry_after = response
+                        .headers()
+                        .get(reqwest::header::RETRY_AFTER)
+                        .and_then(|value| value.to_str().ok())
+                        .and_then(|value| value.parse().ok());
+                    Attempt::Retry(Error::network(url, status), retry_after)
+                }
+                status if !status.is_success() => Attempt::Failed(Error::network(url, status)),
+                _ => Attempt::Done(response),
+            },
+            Err(e) if is_transient(&e) => Attempt::Retry(Error::network(url, e), None),
+            Err(e) => Attempt::Failed(Error::network(url, e)),
+        }
+    }
+
+    /// Sends a request, retrying with exponential backoff on connection errors, timeouts,
+    /// server errors and `429 Too Many Requests`
+    async fn send(&self, method: reqwest::Method, url: &str) -> Result<reqwest::Response> {
+        let mut retry = 0;
+        loop {
+            match self.try_send(method.clone(), url).await {
+                Attempt::Done(response) => return Ok(response),
+                Attempt::Failed(error) => return Err(error),
+                Attempt::Retry(error, _) if retry >= self.config.retries => return Err(error),
+                Attempt::Retry(_, retry_after) => {
+                    retry += 1;
+                    self.wait(retry, retry_after).await;
+                }
+            }
+        }
+    }
+
+    /// Requests the size of a file without downloading it
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The URL of the file
+    ///
+    /// # Returns
+    ///
+    /// The `Content-Length` of the file, or `None` if the server does not report it
+    pub async fn size(&self, url: &str) -> Result<Option<u64>> {
+        let _permit = self.permits.acquire().await.expect("semaphore closed");
+        let response = self.send(reqwest::Method::HEAD, url).await?;
+        Ok(response
+            .headers()
+            .get(reqwest::header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok()))
+    }
+
+    /// Downloads the whole response body, retrying if the transfer is interrupted
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The URL to request
+    pub async fn get_bytes(&self, url: &str) -> Result<Vec<u8>> {
+        Ok(self.get_content(url, None).await?.bytes)
+    }
+
+    /// Downloads the whole response body unless it exceeds a maximum size
+    ///
+    /// Requests are retried with exponential backoff on connection errors, timeouts, server
+    /// errors and `429 Too Many Requests`, and if the transfer is interrupted or the body is
+    /// shorter than announced by the server.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The URL to request
+    /// * `limit` - Maximum number of bytes to download
+    ///
+    /// # Returns
+    ///
+    /// The response body, `Error::NotFound` for a 404 status, or `Error::InvalidInput` if it is
+    /// larger than `limit`
+    pub async fn get_content(&self, url: &str, limit: Option<u64>) -> Result<Content> {
+        let _permit = self.permits.acquire().await.expect("semaphore closed");
+        let mut retry = 0;
+        loop {
+            let attempt = match self.try_send(reqwest::Method::GET, url).await {
+                Attempt::Done(response) => read_body(url, response, limit).await,
+                Attempt::Retry(error, retry_after) => Attempt::Retry(error, retry_after),
+                Attempt::Failed(error) => Attempt::Failed(error),
+            };
+            match attempt {
+                Attempt::Done(content) => return Ok(content),
+                Attempt::Failed(error) => return Err(error),
+                Attempt::Retry(error, _) if retry >= self.config.retries => return Err(error),
+                Attempt::Retry(_, retry_after) => {
+                    retry += 1;
+                    self.wait(retry, retry_after).await;
+                }
+            }
+        }
+    }
+
+    /// Downloads the response body as text, retrying if the transfer is interrupted
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The URL to request
+    pub async fn get_text(&self, url: &str) -> Result<String> {
+        let bytes = self.get_bytes(url).await?;
+        Ok(String::from_utf8_lossy(&bytes).to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Checks that missing settings of the download section fall back to their defaults
+    fn test_download_config() {
+        let config: DownloadConfig = serde_yaml::from_str("concurrency: 2").unwrap();
+        assert_eq!(config.concurrency, 2);
+        assert_eq!(config.retries, DownloadConfig::default().retries);
+    }
+
+    #[test]
+    /// Checks that artifacts and folders are selected by the include and exclude patterns
+    fn test_artifact_filter() {
+        let filter = ArtifactFilter::new(&DownloadConfig {
+            include: vec![
+                "build-log.txt".to_string(),
+                "artifacts/*/junit*.xml".to_string(),
+                "**/*.log".to_string(),
+            ],
+            exclude: vec![
+                "**/must-gather/**".to_string(),
+                "**/gather-extra".to_string(),
+            ],
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(filter.is_selected("build-log.txt"));
+        assert!(!filter.is_selected("artifacts/build-log.txt"));
+        assert!(filter.is_selected("artifacts/e2e/junit_01.xml"));
+        assert!(filter.is_selected("artifacts/e2e/pods/kubelet.log"));
+        assert!(!filter.is_selected("artifacts/e2e/must-gather/kubelet.log"));
+        assert!(!filter.is_selected("artifacts/e2e/image.tar"));
+        assert!(!filter.is_skipped_folder("artifacts/e2e"));
+        assert!(filter.is_skipped_folder("artifacts/e2e/must-gather"));
+        assert!(filter.is_skipped_folder("artifacts/e2e/gather-extra/"));
+
+        let filter = ArtifactFilter::new(&DownloadConfig {
+            include: vec![
+                "build-log.txt".to_string(),
+                "artifacts/*/junit*.xml".to_string(),
+            ],
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(!filter.is_skipped_folder("artifacts/e2e"));
+        assert!(filter.is_skipped_folder("artifacts/e2e/pods"));
+        assert!(filter.is_skipped_folder("logs"));
+        assert!(ArtifactFilter::new(&DownloadConfig {
+            exclude: vec!["[".to_string()],
+            ..Default::default()
+        })
+        .is_err());
+    }
+}
@@ -0,0 +1,156 @@
+use crate::collection::download::downloader;
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, skip_serializing_none, DisplayFromStr};
+
+#[skip_serializing_none]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+/// `gcs` section of the download settings
+pub struct GcsConfig {
+    /// If set to false, artifacts are always found through the HTML listings of the artifact pages
+    pub enabled: bool,
+    /// URL of the Google Cloud Storage JSON API, e.g. of a local fake-gcs-server for tests
+    pub endpoint: String,
+}
+
+impl Default for GcsConfig {
+    fn default() -> Self {
+        GcsConfig {
+            enabled: true,
+            endpoint: "https://storage.googleapis.com".to_string(),
+        }
+    }
+}
+
+#[serde_as]
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// An object of a bucket as listed by the JSON API
+pub struct GcsObject {
+    /// Full name of the object including its prefix
+    pub name: String,
+    /// Size in bytes, which the API reports as a string
+    #[serde_as(as = "DisplayFromStr")]
+    pub size: u64,
+}
+
+#[derive(Default, Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ObjectList {
+    #[serde(default)]
+    items: Vec<GcsObject>,
+    next_page_token: Option<String>,
+}
+
+/// Percent-encodes a path segment or query value, keeping only unreserved characters
+fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+fn endpoint() -> String {
+    downloader()
+        .gcs()
+        .endpoint
+        .trim_end_matches('/')
+        .to_string()
+}
+
+/// URL from which an object is downloaded through the JSON API
+///
+/// # Arguments
+///
+/// * `bucket` - Name of the bucket, without `gs://`
+/// * `name` - Full name of the object
+pub fn object_url(bucket: &str, name: &str) -> String {
+    format!(
+        "{}/storage/v1/b/{}/o/{}?alt=media",
+        endpoint(),
+        encode(bucket),
+        encode(name)
+    )
+}
+
+/// Lists all objects of a bucket below a prefix, following every page of the listing
+///
+/// Requests are sent anonymously, so only public buckets can be listed.
+///
+/// # Arguments
+///
+/// * `bucket` - Name of the bucket, without `gs://`
+/// * `prefix` - Prefix the names of the objects start with, e.g. `logs/<job>/<build_id>/`
+///
+/// # Returns
+///
+/// The listed objects, or `Error::NotFound` if the bucket does not exist
+pub async fn list_objects(bucket: &str, prefix: &str) -> Result<Vec<GcsObject>> {
+    let list_url = format!(
+        "{}/storage/v1/b/{}/o?prefix={}",
+        endpoint(),
+        encode(bucket),
+        encode(prefix)
+    );
+    let mut objects = Vec::new();
+    let mut page_token: Option<String> = None;
+    loop {
+        let url = match &page_token {
+            Some(token) => format!("{}&pageToken={}", list_url, encode(token)),
+            None => list_url.clone(),
+        };
+        let content = downloader().get_bytes(&url).await?;
+        let list: ObjectList =
+            serde_json::from_slice(&content).map_err(|e| Error::parse(&url, e))?;
+        objects.extend(list.items);
+        match list.next_page_token {
+            Some(token) if !token.is_empty() => page_token = Some(token),
+            _ => return Ok(objects),
+        }
+    }
+}
+
+/// Splits a `gs://<bucket>/<path>` URL into its bucket and path
+///
+/// # Returns
+///
+/// Bucket and path, or `None` if the URL does not point into a GCS bucket
+pub fn split_bucket_url(url: &str) -> Option<(String, String)> {
+    let location = url.strip_prefix("gs://")?;
+    let (bucket, path) = location.split_once('/').unwrap_or((location, ""));
+    match bucket.is_empty() {
+        true => None,
+        false => Some((bucket.to_string(), path.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Checks that object names are encoded and listings with sizes given as strings are parsed
+    fn test_gcs_objects() {
+        assert_eq!(
+            object_url("origin-ci-test", "logs/job/1/build-log.txt"),
+            "https://storage.googleapis.com/storage/v1/b/origin-ci-test/o/logs%2Fjob%2F1%2Fbuild-log.txt?alt=media"
+        );
+        let list: ObjectList = serde_json::from_str(
+            r#"{"kind": "storage#objects", "nextPageToken": "abc",
+                "items": [{"name": "logs/job/1/build-log.txt", "size": "42", "md5Hash": "x"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(list.items[0].size, 42);
+        assert_eq!(list.next_page_token.as_deref(), Some("abc"));
+        assert_eq!(
+            split_bucket_url("gs://kubernetes-jenkins/logs/job/1"),
+            Some(("kubernetes-jenkins".to_string(), "logs/job/1".to_string()))
+        );
+        assert_eq!(split_bucket_url("s3://bucket/logs"), None);
+    }
+}
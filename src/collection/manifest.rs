@@ -0,0 +1,167 @@
+use crate::collection::download::Content;
+use crate::error::Result;
+use crate::system::{check_slash, read_json, write_atomic, write_json};
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Name of the manifest file within the artifact folder of a build
+pub const MANIFEST_FILE: &str = ".arcalog-manifest.json";
+
+/// Returns true for files that Arcalog writes into artifact folders itself, i.e. the manifest and
+/// partially written files, which must not be analysed as artifacts
+///
+/// # Arguments
+///
+/// * `path` - Path of the file
+pub fn is_internal_file(path: &str) -> bool {
+    path.ends_with(".part")
+        || Path::new(path)
+            .file_name()
+            .is_some_and(|name| name == MANIFEST_FILE)
+}
+
+/// Computes the hex-encoded SHA-256 checksum of a file content
+pub fn sha256(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[skip_serializing_none]
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// A downloaded artifact as recorded in the manifest
+pub struct ManifestEntry {
+    pub url: String,
+    /// Size in bytes
+    pub size: u64,
+    /// Entity tag of the file as reported by the server
+    pub etag: Option<String>,
+    pub sha256: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Records size and checksum of every downloaded artifact of a build
+pub struct Manifest {
+    /// Entries by path relative to the artifact folder of the build
+    pub files: BTreeMap<String, ManifestEntry>,
+    /// Set once all artifacts of the build have been listed and downloaded without failures
+    #[serde(default)]
+    pub complete: bool,
+}
+
+impl Manifest {
+    /// Loads the manifest of a build
+    ///
+    /// # Arguments
+    ///
+    /// * `artifact_path` - Path to where the artifacts of the build are stored
+    ///
+    /// # Returns
+    ///
+    /// The manifest, which is empty if none has been written yet
+    pub fn load(artifact_path: &str) -> Result<Self> {
+        let path = format!("{}{}", check_slash(artifact_path), MANIFEST_FILE);
+        if !Path::new(&path).exists() {
+            return Ok(Manifest::default());
+        }
+        read_json(&path)
+    }
+
+    /// Writes the manifest of a build
+    ///
+    /// # Arguments
+    ///
+    /// * `artifact_path` - Path to where the artifacts of the build are stored
+    pub fn save(&self, artifact_path: &str) -> Result<()> {
+        write_json(
+            &format!("{}{}", check_slash(artifact_path), MANIFEST_FILE),
+            self,
+        )
+    }
+
+    /// Checks a downloaded artifact against its entry
+    ///
+    /// # Arguments
+    ///
+    /// * `artifact_path` - Path to where the artifacts of the build are stored
+    /// * `path` - Path of the artifact relative to `artifact_path`
+    ///
+    /// # Returns
+    ///
+    /// `Some(true)` if size and checksum match, `Some(false)` if they do not or the file cannot be
+    /// read, and `None` if the artifact has no entry
+    pub fn verify(&self, artifact_path: &str, path: &str) -> Option<bool> {
+        let entry = self.files.get(path)?;
+        let Ok(content) = std::fs::read(format!("{}{}", check_slash(artifact_path), path)) else {
+            return Some(false);
+        };
+        Some(content.len() as u64 == entry.size && sha256(&content) == entry.sha256)
+    }
+}
+
+/// Writes a downloaded artifact atomically and creates its manifest entry
+///
+/// # Arguments
+///
+/// * `target_file` - Path of the artifact file
+/// * `url` - URL the artifact was downloaded from
+/// * `content` - The downloaded artifact
+///
+/// # Returns
+///
+/// The `ManifestEntry` of the artifact
+pub fn save_artifact(target_file: &str, url: &str, content: &Content) -> Result<ManifestEntry> {
+    write_atomic(target_file, &content.bytes)?;
+    Ok(ManifestEntry {
+        url: url.to_string(),
+        size: content.bytes.len() as u64,
+        etag: content.etag.clone(),
+        sha256: sha256(&content.bytes),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Checks that truncated artifacts are detected and internal files are recognised
+    fn test_manifest_verify() {
+        let artifact_path =
+            std::env::temp_dir().join(format!("arcalog-manifest-{}", std::process::id()));
+        let artifact_path = artifact_path.to_string_lossy().to_string();
+        std::fs::create_dir_all(&artifact_path).unwrap();
+        let content = Content {
+            bytes: b"line 1\nline 2\n".to_vec(),
+            etag: Some("\"1\"".to_string()),
+        };
+        let mut manifest = Manifest::default();
+        let entry =
+            save_artifact(&format!("{}/build-log.txt", artifact_path), "url", &content).unwrap();
+        manifest.files.insert("build-log.txt".to_string(), entry);
+        manifest.save(&artifact_path).unwrap();
+
+        let manifest = Manifest::load(&artifact_path).unwrap();
+        assert_eq!(manifest.verify(&artifact_path, "build-log.txt"), Some(true));
+        assert_eq!(manifest.verify(&artifact_path, "other.txt"), None);
+        std::fs::write(format!("{}/build-log.txt", artifact_path), b"line 1\n").unwrap();
+        assert_eq!(
+            manifest.verify(&artifact_path, "build-log.txt"),
+            Some(false)
+        );
+        assert!(is_internal_file(&format!(
+            "{}/{}",
+            artifact_path, MANIFEST_FILE
+        )));
+        assert!(is_internal_file("artifacts/build-log.txt.part"));
+        // Manifests written before downloads were tracked as complete are downloaded again
+        let manifest: Manifest = serde_json::from_str(r#"{"files": {}}"#).unwrap();
+        assert!(!manifest.complete);
+        assert!(!is_internal_file("artifacts/build-log.txt"));
+        std::fs::remove_dir_all(&artifact_path).unwrap();
+    }
+}
@@ -1,12 +1,23 @@
-use crate::identification::collect_events;
+use crate::analysis::classifier::{Classifier, Prediction};
+use crate::analysis::labelling::{load_label, move_legacy_labels, HumanLabel};
+use crate::analysis::rules::FiredRule;
+use crate::collection::download::{downloader, DownloadSummary, FailedFile, SkippedFile};
+use crate::collection::gcs::{self, split_bucket_url};
+use crate::collection::manifest::{save_artifact, sha256, Manifest, ManifestEntry};
+use crate::collection::{check_instance, instance_name, BuildRecord, BuildState, Collector};
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::identification::junit::{collect_test_results, test_results_path};
+use crate::identification::templates::{mine_templates, Template};
+use crate::identification::{collect_events, Event};
+use crate::index::{BuildIndex, JobVersion, StoredJobs, Transition};
 use crate::system::*;
 use async_recursion::async_recursion;
+use async_trait::async_trait;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use serde_json;
 use serde_with::*;
 use std::collections::HashMap;
-use std::fs::File;
 use std::path::Path;
 use std::vec;
 
@@ -17,32 +28,104 @@ pub struct Root {
     pub items: Vec<Item>,
 }
 
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+/// Prow jobs metadata whose jobs have not been parsed yet, so that each can be parsed on its own
+struct RawRoot {
+    items: Vec<serde_json::Value>,
+}
+
+/// Parses the Prow jobs metadata as listed in `prowjobs.js`
+///
+/// Jobs that cannot be parsed, e.g. because a Prow instance is configured differently than
+/// expected, are skipped and reported instead of failing the whole listing.
+///
+/// # Arguments
+///
+/// * `content` - The Prow jobs metadata
+/// * `source` - Where the metadata comes from, used in messages
+///
+/// # Returns
+///
+/// The parsed jobs, or a parse error if the metadata is not a list of jobs at all
+pub fn parse_items(content: &[u8], source: &str) -> Result<Vec<Item>> {
+    let root: RawRoot = serde_json::from_slice(content).map_err(|e| Error::parse(source, e))?;
+    let mut items = Vec::new();
+    let mut skipped = Vec::new();
+    for value in root.items {
+        match Item::deserialize(&value) {
+            Ok(mut item) => {
+                item.raw = Some(value);
+                items.push(item);
+            }
+            Err(e) => {
+                let name = value
+                    .pointer("/metadata/name")
+                    .and_then(|name| name.as_str())
+                    .unwrap_or("unnamed job")
+                    .to_string();
+                skipped.push((name, e));
+            }
+        }
+    }
+    if !skipped.is_empty() {
+        println!(
+            "⛔\t\x1b[93m\x1b[1m{} of {} jobs of {} could not be parsed and were skipped:\x1b[0m",
+            skipped.len(),
+            items.len() + skipped.len(),
+            source
+        );
+        for (name, e) in skipped {
+            println!("\t{}: {}", name, e);
+        }
+    }
+    Ok(items)
+}
+
 #[skip_serializing_none]
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 /// Item struct containing each individual Prow job's metadata
 pub struct Item {
+    #[serde(default)]
     pub kind: String,
     #[serde(rename = "apiVersion")]
+    #[serde(default)]
     pub api_version: String,
     pub metadata: Metadata,
     pub spec: Spec,
     /// Status of each job
+    #[serde(default)]
     pub status: Status,
+    /// Fields that Arcalog does not model
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+    /// The job exactly as listed by Prow, which is stored instead of the parsed job so that no
+    /// field is lost
+    #[serde(skip)]
+    pub raw: Option<serde_json::Value>,
 }
 
 #[skip_serializing_none]
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Metadata {
+    #[serde(default)]
     pub name: String,
+    #[serde(default)]
     pub namespace: String,
     pub uid: String,
     #[serde(rename = "resourceVersion")]
     pub resource_version: String,
+    #[serde(default)]
     pub generation: i64,
     #[serde(rename = "creationTimestamp")]
+    #[serde(default)]
     pub creation_timestamp: String,
+    #[serde(default)]
     pub labels: Labels,
+    #[serde(default)]
     pub annotations: Annotations,
+    /// Fields that Arcalog does not model
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[skip_serializing_none]
@@ -60,8 +143,10 @@ pub struct Labels {
     pub prow_k8s_io_id: Option<String>,
     /// Job type
     #[serde(rename = "prow.k8s.io/job")]
+    #[serde(default)]
     pub prow_k8s_io_job: String,
     #[serde(rename = "prow.k8s.io/type")]
+    #[serde(default)]
     pub prow_k8s_io_type: String,
     #[serde(rename = "preset-dind-enabled")]
     pub preset_dind_enabled: Option<String>,
@@ -185,6 +270,9 @@ pub struct Labels {
     pub release_openshift_io_payload: Option<String>,
     #[serde(rename = "release.openshift.io/verify")]
     pub release_openshift_io_verify: Option<String>,
+    /// Fields that Arcalog does not model
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[skip_serializing_none]
@@ -194,6 +282,7 @@ pub struct Annotations {
     #[serde(rename = "prow.k8s.io/context")]
     pub prow_k8s_io_context: Option<String>,
     #[serde(rename = "prow.k8s.io/job")]
+    #[serde(default)]
     pub prow_k8s_io_job: String,
     #[serde(rename = "testgrid-dashboards")]
     pub testgrid_dashboards: Option<String>,
@@ -257,15 +346,22 @@ pub struct Annotations {
     pub release_openshift_io_source: Option<String>,
     #[serde(rename = "release.openshift.io/tag")]
     pub release_openshift_io_tag: Option<String>,
+    /// Fields that Arcalog does not model
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[skip_serializing_none]
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Spec {
     #[serde(rename = "type")]
+    #[serde(default)]
     pub type_field: String,
+    #[serde(default)]
     pub agent: String,
+    #[serde(default)]
     pub cluster: String,
+    #[serde(default)]
     pub namespace: String,
     pub job: String,
     pub report: Option<bool>,
@@ -280,6 +376,9 @@ pub struct Spec {
     pub context: Option<String>,
     pub rerun_command: Option<String>,
     pub reporter_config: Option<ReporterConfig>,
+    /// Fields that Arcalog does not model
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[skip_serializing_none]
@@ -287,6 +386,7 @@ pub struct Spec {
 pub struct PodSpec {
     #[serde(default)]
     pub volumes: Vec<Volume>,
+    #[serde(default)]
     pub containers: Vec<Container>,
     #[serde(rename = "serviceAccountName")]
     pub service_account_name: Option<String>,
@@ -300,6 +400,9 @@ pub struct PodSpec {
     pub host_network: Option<bool>,
     #[serde(rename = "hostPID")]
     pub host_pid: Option<bool>,
+    /// Fields that Arcalog does not model
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[skip_serializing_none]
@@ -319,6 +422,7 @@ pub struct Volume {
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Secret {
     #[serde(rename = "secretName")]
+    #[serde(default)]
     pub secret_name: String,
     #[serde(rename = "defaultMode")]
     pub default_mode: Option<i64>,
@@ -343,6 +447,7 @@ pub struct EmptyDir {}
 pub struct HostPath {
     pub path: String,
     #[serde(rename = "type")]
+    #[serde(default)]
     pub type_field: String,
 }
 
@@ -355,7 +460,9 @@ pub struct ConfigMap {
 #[skip_serializing_none]
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Container {
+    #[serde(default)]
     pub name: String,
+    #[serde(default)]
     pub image: String,
     #[serde(default)]
     pub command: Vec<String>,
@@ -373,6 +480,9 @@ pub struct Container {
     #[serde(rename = "envFrom")]
     #[serde(default)]
     pub env_from: Vec<EnvFrom>,
+    /// Fields that Arcalog does not model
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[skip_serializing_none]
@@ -424,7 +534,7 @@ pub struct Requests {
 #[skip_serializing_none]
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Limits {
-    pub memory: String,
+    pub memory: Option<String>,
     pub cpu: Option<String>,
 }
 
@@ -441,13 +551,14 @@ pub struct VolumeMount {
 #[skip_serializing_none]
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SecurityContext {
-    pub privileged: bool,
+    pub privileged: Option<bool>,
     pub capabilities: Option<Capabilities>,
 }
 
 #[skip_serializing_none]
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Capabilities {
+    #[serde(default)]
     pub add: Vec<String>,
 }
 
@@ -455,7 +566,9 @@ pub struct Capabilities {
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EnvFrom {
     #[serde(rename = "secretRef")]
-    pub secret_ref: SecretRef,
+    pub secret_ref: Option<SecretRef>,
+    #[serde(rename = "configMapRef")]
+    pub config_map_ref: Option<SecretRef>,
 }
 
 #[skip_serializing_none]
@@ -468,111 +581,146 @@ pub struct SecretRef {
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SecurityContext2 {
     #[serde(rename = "runAsUser")]
-    pub run_as_user: i64,
+    pub run_as_user: Option<i64>,
 }
 
 #[skip_serializing_none]
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct NodeSelector {
-    pub highcpu: String,
+    pub highcpu: Option<String>,
+    /// Other node labels to select nodes by
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[skip_serializing_none]
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Toleration {
+    #[serde(default)]
     pub key: String,
+    #[serde(default)]
     pub operator: String,
-    pub value: String,
+    pub value: Option<String>,
+    #[serde(default)]
     pub effect: String,
 }
 
 #[skip_serializing_none]
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DecorationConfig {
+    #[serde(default)]
     pub timeout: String,
+    #[serde(default)]
     pub grace_period: String,
+    #[serde(default)]
     pub utility_images: UtilityImages,
     pub resources: Option<Resources2>,
-    pub gcs_configuration: GcsConfiguration,
-    pub gcs_credentials_secret: String,
+    /// Where the artifacts are uploaded to, unless another storage like S3 is used
+    pub gcs_configuration: Option<GcsConfiguration>,
+    pub gcs_credentials_secret: Option<String>,
+    /// Fields that Arcalog does not model
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[skip_serializing_none]
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct UtilityImages {
+    #[serde(default)]
     pub clonerefs: String,
+    #[serde(default)]
     pub initupload: String,
+    #[serde(default)]
     pub entrypoint: String,
+    #[serde(default)]
     pub sidecar: String,
 }
 
 #[skip_serializing_none]
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Resources2 {
+    #[serde(default)]
     pub clonerefs: Clonerefs,
+    #[serde(default)]
     pub initupload: Initupload,
+    #[serde(default)]
     pub place_entrypoint: PlaceEntrypoint,
+    #[serde(default)]
     pub sidecar: Sidecar,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Clonerefs {
+    #[serde(default)]
     pub requests: Requests2,
 }
 
 #[skip_serializing_none]
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Requests2 {
+    #[serde(default)]
     pub cpu: String,
 }
 
 #[skip_serializing_none]
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Initupload {
+    #[serde(default)]
     pub requests: Requests3,
 }
 
 #[skip_serializing_none]
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Requests3 {
+    #[serde(default)]
     pub cpu: String,
 }
 
 #[skip_serializing_none]
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PlaceEntrypoint {
+    #[serde(default)]
     pub requests: Requests4,
 }
 
 #[skip_serializing_none]
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Requests4 {
+    #[serde(default)]
     pub cpu: String,
 }
 
 #[skip_serializing_none]
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Sidecar {
+    #[serde(default)]
     pub requests: Requests5,
 }
 
 #[skip_serializing_none]
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Requests5 {
+    #[serde(default)]
     pub cpu: String,
 }
 
 #[skip_serializing_none]
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct GcsConfiguration {
+    #[serde(default)]
     pub bucket: String,
+    /// Path within the bucket that the paths of all builds start with
+    pub path_prefix: Option<String>,
+    /// `legacy`, `single` or `explicit`, deciding how repositories are named in the paths of presubmit builds
+    #[serde(default)]
     pub path_strategy: String,
-    pub default_org: String,
-    pub default_repo: String,
+    pub default_org: Option<String>,
+    pub default_repo: Option<String>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ProwjobDefaults {
+    #[serde(default)]
     pub tenant_id: String,
 }
 
@@ -596,6 +744,7 @@ pub struct GithubTeamSlug {
 pub struct ExtraRef {
     pub org: String,
     pub repo: String,
+    #[serde(default)]
     pub base_ref: String,
     pub path_alias: Option<String>,
     pub workdir: Option<bool>,
@@ -608,6 +757,7 @@ pub struct Refs {
     pub org: String,
     pub repo: String,
     pub repo_link: Option<String>,
+    #[serde(default)]
     pub base_ref: String,
     pub base_sha: Option<String>,
     pub base_link: Option<String>,
@@ -620,8 +770,11 @@ pub struct Refs {
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Pull {
     pub number: i64,
+    #[serde(default)]
     pub author: String,
+    #[serde(default)]
     pub sha: String,
+    #[serde(default)]
     pub title: String,
     pub link: Option<String>,
     pub commit_link: Option<String>,
@@ -631,15 +784,18 @@ pub struct Pull {
 #[skip_serializing_none]
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ReporterConfig {
-    pub slack: Slack,
+    pub slack: Option<Slack>,
 }
 
 #[skip_serializing_none]
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Slack {
+    #[serde(default)]
     pub channel: String,
+    #[serde(default)]
     pub job_states_to_report: Vec<String>,
     pub report_template: Option<String>,
+    #[serde(default)]
     pub report: bool,
 }
 
@@ -654,12 +810,15 @@ pub struct Status {
     #[serde(rename = "completionTime")]
     pub completion_time: Option<String>,
     /// Indicates job state, including `failure`, `pending`, `success`, and more as defined in Prow
-    pub state: Option<String>,
+    pub state: Option<BuildState>,
     pub description: Option<String>,
     pub url: Option<String>,
     pub pod_name: Option<String>,
     pub build_id: Option<String>,
     pub prev_report_states: Option<PrevReportStates>,
+    /// Fields that Arcalog does not model
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[skip_serializing_none]
@@ -680,250 +839,1191 @@ pub struct JobTypeBuilds {
     pub builds: Vec<String>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Default, Debug, Deserialize, Serialize)]
 pub struct BuildInfo {
     pub build_id: String,
+    /// Name of the Prow instance the build was collected from
+    pub instance: Option<String>,
     pub build_url: Option<String>,
     pub label: Option<String>,
+    /// State of the build as displayed, e.g. `⛔ failure`
     pub state: Option<String>,
+    /// State of the build as reported by the collector
+    pub build_state: Option<BuildState>,
     pub job_type: Option<String>,
-    pub events: Option<Vec<String>>,
+    pub events: Option<Vec<Event>>,
+    /// Names of the test cases that failed according to the JUnit reports of the build
+    pub failing_tests: Option<Vec<String>>,
+    /// Events grouped into near-identical templates, most frequent first
+    pub templates: Option<Vec<Template>>,
+    /// Classification rules that fired for a failed build, the rule deciding the label first
+    pub rules: Option<Vec<FiredRule>>,
+    /// Label predicted for a failed build by the trained classifier
+    pub prediction: Option<Prediction>,
+    /// Root cause recorded by an engineer, which takes precedence over rules and predictions
+    pub human_label: Option<HumanLabel>,
+    /// Run time of the build in seconds
+    pub duration: Option<i64>,
+    /// State changes of the build observed during collection, oldest first
+    pub transitions: Option<Vec<Transition>>,
+    pub result: Option<String>,
+    pub revision: Option<String>,
+    pub node: Option<String>,
     pub error: Option<String>,
 }
 
-/// Downloads the latest Prow jobs metadata and creates searchable files
-///
-///  # Arguments
-///
-/// * `location` - The URL from which Prow metadata needs to be downloaded
-/// * `volume` - The data path where the files will be stored
-/// * `artifactions_collection` - If set to true, the function will also download all artifacts of all builds in the current collection step
-pub async fn download_metadata(location: &str, volume: &str, artifacts_collection: bool) {
-    println!("🔍\t\x1b[32m\x1b[1mCollecting metadata from Prow...\x1b[0m");
-    let time_id = add_time_id();
-    let volume_slash = check_slash(&volume);
-    let folder_data = format!("{}prow", &volume_slash);
-    let folder_success = format!("{}prow/success", &volume_slash);
-    let folder_failure = format!("{}prow/failure", &volume_slash);
-    let folder_type = format!("{}prow/type", &volume_slash);
+#[skip_serializing_none]
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Content of the `started.json` file in the artifact folder of a build
+pub struct Started {
+    /// Start time as a Unix timestamp
+    pub timestamp: Option<i64>,
+    pub node: Option<String>,
+    #[serde(rename = "repo-version")]
+    pub repo_version: Option<String>,
+    pub pull: Option<String>,
+}
 
-    if !Path::new(&folder_data).exists() {
-        std::fs::create_dir_all(&folder_data)
-            .expect(format!("Failed to create directory: {}", &folder_data).as_str());
+#[skip_serializing_none]
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Content of the `finished.json` file in the artifact folder of a build
+pub struct Finished {
+    /// End time as a Unix timestamp
+    pub timestamp: Option<i64>,
+    pub passed: Option<bool>,
+    pub result: Option<String>,
+    pub revision: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+/// A Prow instance to collect from, given either as its URL or with an alias naming its stored data
+pub enum ProwLocation {
+    Url(String),
+    Instance { url: String, alias: Option<String> },
+}
+
+impl ProwLocation {
+    /// URL of the Prow instance
+    pub fn url(&self) -> &str {
+        match self {
+            ProwLocation::Url(url) => url,
+            ProwLocation::Instance { url, .. } => url,
+        }
     }
-    if !Path::new(&folder_success).exists() {
-        std::fs::create_dir_all(&folder_success)
-            .expect(format!("Failed to create directory: {}", &folder_success).as_str());
+
+    /// Name under which the data of the instance is stored, which is its alias or else its host
+    pub fn instance(&self) -> String {
+        match self {
+            ProwLocation::Instance {
+                alias: Some(alias), ..
+            } => alias.clone(),
+            location => instance_name(location.url()),
+        }
     }
-    if !Path::new(&folder_failure).exists() {
-        std::fs::create_dir_all(&folder_failure)
-            .expect(format!("Failed to create directory: {}", &folder_failure).as_str());
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Configuration section of the Prow collector
+pub struct ProwConfig {
+    /// The Prow instances to collect from
+    pub location: Vec<ProwLocation>,
+    /// Selects the jobs that are collected
+    #[serde(default)]
+    pub parameters: ProwParameters,
+}
+
+#[skip_serializing_none]
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+/// Selects the Prow jobs that are indexed and whose artifacts are downloaded
+///
+/// A job has to match every setting, and settings that are left empty match every job.
+pub struct ProwParameters {
+    /// Regular expressions of which at least one has to match the whole job name
+    pub jobs: Vec<String>,
+    /// Job types, i.e. `presubmit`, `postsubmit`, `periodic` or `batch`
+    pub types: Vec<String>,
+    /// Organizations (e.g. `kubernetes`) or repositories (e.g. `kubernetes/kubernetes`) the job runs against
+    pub repos: Vec<String>,
+    /// Job states, e.g. `success`, `failure`, `error` or `pending`
+    pub states: Vec<BuildState>,
+    /// Maximum age of a job in days, counted from its start
+    pub max_age_days: Option<u32>,
+}
+
+/// Compiled `ProwParameters` deciding which Prow jobs are collected
+#[derive(Default, Debug, Clone)]
+pub struct JobFilter {
+    jobs: Vec<Regex>,
+    parameters: ProwParameters,
+}
+
+impl JobFilter {
+    /// Compiles the job name patterns of the parameters
+    ///
+    /// # Arguments
+    ///
+    /// * `parameters` - The `parameters` of the `prow` section of the configuration file
+    ///
+    /// # Returns
+    ///
+    /// The `JobFilter`, or a parse error if a job name pattern is invalid
+    pub fn new(parameters: &ProwParameters) -> Result<Self> {
+        let jobs = parameters
+            .jobs
+            .iter()
+            .map(|job| {
+                Regex::new(&format!("^(?:{})$", job))
+                    .map_err(|e| Error::parse("prow job pattern", e))
+            })
+            .collect::<Result<Vec<Regex>>>()?;
+        Ok(JobFilter {
+            jobs,
+            parameters: parameters.clone(),
+        })
     }
-    if !Path::new(&folder_type).exists() {
-        std::fs::create_dir_all(&folder_type)
-            .expect(format!("Failed to create directory: {}", &folder_type).as_str());
+
+    /// Returns true if the job is selected by all parameters
+    ///
+    /// # Arguments
+    ///
+    /// * `item` - The Prow job
+    /// * `now` - The current time, from which the age of the job is counted
+    pub fn is_selected(&self, item: &Item, now: chrono::DateTime<chrono::Utc>) -> bool {
+        let parameters = &self.parameters;
+        if !self.jobs.is_empty() && !self.jobs.iter().any(|job| job.is_match(&item.spec.job)) {
+            return false;
+        }
+        if !parameters.types.is_empty()
+            && !parameters
+                .types
+                .iter()
+                .any(|job_type| job_type.eq_ignore_ascii_case(&item.spec.type_field))
+        {
+            return false;
+        }
+        if !parameters.states.is_empty()
+            && !item
+                .status
+                .state
+                .as_ref()
+                .is_some_and(|state| parameters.states.contains(state))
+        {
+            return false;
+        }
+        if !parameters.repos.is_empty() {
+            // Periodic jobs usually only have extra refs
+            let mut repos = item
+                .spec
+                .extra_refs
+                .iter()
+                .map(|refs| (refs.org.as_str(), refs.repo.as_str()))
+                .chain(
+                    item.spec
+                        .refs
+                        .iter()
+                        .map(|refs| (refs.org.as_str(), refs.repo.as_str())),
+                );
+            let selected = repos.any(|(org, repo)| {
+                parameters
+                    .repos
+                    .iter()
+                    .any(|selected| match selected.split_once('/') {
+                        Some((selected_org, selected_repo)) => {
+                            selected_org.eq_ignore_ascii_case(org)
+                                && selected_repo.eq_ignore_ascii_case(repo)
+                        }
+                        None => selected.eq_ignore_ascii_case(org),
+                    })
+            });
+            if !selected {
+                return false;
+            }
+        }
+        if let Some(max_age_days) = parameters.max_age_days {
+            let started = item
+                .status
+                .start_time
+                .as_deref()
+                .unwrap_or(&item.metadata.creation_timestamp);
+            // Jobs without a valid start time are kept, as their age is unknown
+            if let Ok(started) = chrono::DateTime::parse_from_rfc3339(started) {
+                if now.signed_duration_since(started) > chrono::Duration::days(max_age_days as i64)
+                {
+                    return false;
+                }
+            }
+        }
+        true
     }
+}
 
-    let path = format!("{}prow/collect-{}.json", &volume_slash, &time_id);
-    let collect_url = check_slash(&location);
-    let items: Root = reqwest::get(format!("{}prowjobs.js", collect_url).as_str())
-        .await
-        .expect("Failed to download Prow metadata")
-        .json()
-        .await
-        .expect("Failed to parse Prow metadata");
-    serde_json::to_writer_pretty(&File::create(&path).expect("Failed to create file"), &items)
-        .expect("Failed to write JSON to file");
-
-    let mut build_id_failures = HashMap::new();
-    let mut build_id_successes = HashMap::new();
-    let mut job_types = HashMap::new();
-
-    for item in &items.items {
-        let build_id = match item.status.build_id.as_ref() {
-            Some(build_id) => build_id,
-            None => "",
-        };
-        let job_type = item.metadata.labels.prow_k8s_io_job.as_str();
+/// Collector for the Prow CI system
+pub struct ProwCollector {
+    config: ProwConfig,
+    filter: JobFilter,
+}
 
-        if !job_types.contains_key(&job_type) {
-            job_types.insert(job_type, Vec::new());
+impl ProwCollector {
+    /// Creates the collector, checking that every instance has a distinct and valid name and
+    /// that the job name patterns of its parameters are valid
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The `prow` section of the configuration file
+    pub fn new(config: ProwConfig) -> Result<Self> {
+        let mut instances = Vec::new();
+        for location in config
+            .location
+            .iter()
+            .filter(|location| !location.url().is_empty())
+        {
+            let instance = location.instance();
+            check_instance(&instance)?;
+            if instances.contains(&instance) {
+                return Err(Error::InvalidInput(format!(
+                    "Several Prow locations are named {}, please give them distinct aliases",
+                    instance
+                )));
+            }
+            instances.push(instance);
         }
+        let filter = JobFilter::new(&config.parameters)?;
+        Ok(ProwCollector { config, filter })
+    }
+}
+
+#[async_trait]
+impl Collector for ProwCollector {
+    fn name(&self) -> &str {
+        "prow"
+    }
 
-        if !job_types.get(&job_type).unwrap().contains(&build_id) {
-            job_types.get_mut(&job_type).unwrap().push(build_id.clone());
+    async fn download_metadata(&self, data_path: &str, artifacts_collection: bool) -> Result<()> {
+        for location in &self.config.location {
+            if !location.url().is_empty() {
+                download_metadata(
+                    location.url(),
+                    &location.instance(),
+                    data_path,
+                    artifacts_collection,
+                    &self.filter,
+                )
+                .await?;
+            }
         }
+        Ok(())
+    }
 
-        let state = match item.status.state.as_ref() {
-            Some(state) => state,
-            None => "",
-        };
+    async fn download_artifacts(
+        &self,
+        build: &BuildRecord,
+        data_path: &str,
+    ) -> Result<DownloadSummary> {
+        download_artifacts(build, data_path).await
+    }
 
-        if state == "success" {
-            build_id_successes.insert(
-                build_id,
-                [
-                    item.status.url.as_ref().unwrap().to_string(),
-                    item.metadata.labels.prow_k8s_io_job.to_string(),
-                ],
-            );
-        } else if state == "failure" {
-            build_id_failures.insert(
-                build_id,
-                [
-                    item.status.url.as_ref().unwrap().to_string(),
-                    item.metadata.labels.prow_k8s_io_job.to_string(),
-                ],
-            );
-        }
+    async fn find_build(
+        &self,
+        build_id: &str,
+        instance: Option<&str>,
+        data_path: &str,
+    ) -> Result<Option<BuildRecord>> {
+        find_build(build_id, instance, data_path).await
     }
-    let path = format!("{}prow/failure/builds-{}.json", &volume_slash, &time_id);
-    serde_json::to_writer_pretty(
-        &File::create(path).expect("Failed to create job failure file"),
-        &build_id_failures,
-    )
-    .expect("Failed to write failure map to file");
-
-    let path = format!("{}prow/success/builds-{}.json", &volume_slash, &time_id);
-    serde_json::to_writer_pretty(
-        &File::create(path).expect("Failed to create job success file"),
-        &build_id_successes,
-    )
-    .expect("Failed to write success map to file");
-
-    let path = format!("{}prow/type/types-{}.json", &volume_slash, &time_id);
-    serde_json::to_writer_pretty(
-        &File::create(path).expect("Failed to create job types file"),
-        &job_types,
-    )
-    .expect("Failed to write job types map to file");
-    println!("🔍\t\x1b[32m\x1b[1mMetadata collected...\x1b[0m");
+}
+
+/// Downloads the latest Prow jobs metadata and stores the jobs that are new or have changed
+///
+/// Jobs are compared with the stored ones by their `metadata.uid` and `metadata.resourceVersion`,
+/// and every change of their state is recorded in the build index.
+///
+///  # Arguments
+///
+/// * `location` - The URL from which Prow metadata needs to be downloaded
+/// * `instance` - Name under which the data of the Prow instance is stored
+/// * `volume` - The data path where the files will be stored
+/// * `artifactions_collection` - If set to true, the function will also download the artifacts of all listed builds that have finished, unless they have been downloaded completely before
+/// * `filter` - Selects the jobs that are indexed and whose artifacts are downloaded
+pub async fn download_metadata(
+    location: &str,
+    instance: &str,
+    volume: &str,
+    artifacts_collection: bool,
+    filter: &JobFilter,
+) -> Result<()> {
+    println!(
+        "🔍\t\x1b[32m\x1b[1mCollecting metadata from Prow instance {}...\x1b[0m",
+        instance
+    );
+    let volume_slash = check_slash(volume);
+    let collect_url = format!("{}prowjobs.js", check_slash(location));
+    let content = downloader().get_bytes(&collect_url).await?;
+    let items = parse_items(&content, &collect_url)?;
+
+    let now = chrono::Utc::now();
+    let listed = items.len();
+    let items = items
+        .into_iter()
+        .filter(|item| filter.is_selected(item, now))
+        .collect::<Vec<Item>>();
+    if items.len() < listed {
+        println!(
+            "🔍\t\x1b[32m\x1b[1m{} of {} jobs listed by Prow match the parameters\x1b[0m",
+            items.len(),
+            listed
+        );
+    }
+    let observed = now.to_rfc3339();
+    let mut index = open_index(&volume_slash).await?;
+    let stored = store_items(&mut index, instance, &items, &observed)?;
+    println!(
+        "🔍\t\x1b[32m\x1b[1mMetadata collected: {} new or changed and {} unchanged jobs, {} state changes\x1b[0m",
+        stored.updated.len(),
+        stored.unchanged,
+        stored.transitions.len()
+    );
 
     if artifacts_collection {
-        for item in build_id_failures {
-            download_artifacts(item.0, &volume_slash).await;
+        // Builds whose artifacts failed to download, or were not requested, in an earlier
+        // collection are picked up again as long as Prow lists them
+        let finished = items
+            .iter()
+            .filter(|item| {
+                item.status
+                    .state
+                    .as_ref()
+                    .is_some_and(BuildState::is_finished)
+            })
+            .filter_map(|item| build_record(item, instance))
+            .filter(|build| !build.url.is_empty() && !has_all_artifacts(build, &volume_slash));
+        for build in finished {
+            let build = index.get(instance, &build.build_id)?.unwrap_or(build);
+            if let Err(e) = download_artifacts(&build, &volume_slash).await {
+                println!(
+                    "⛔\t\x1b[93m\x1b[1mFailed to download artifacts of build {}: {}\x1b[0m",
+                    build.build_id, e
+                );
+            }
         }
+    }
+    Ok(())
+}
 
-        for item in build_id_successes {
-            download_artifacts(item.0, &volume_slash).await;
-        }
+/// Stores new and changed Prow jobs in the build index
+///
+/// # Arguments
+///
+/// * `index` - The build index
+/// * `instance` - Name of the Prow instance that listed the jobs
+/// * `items` - The Prow jobs as currently listed by Prow
+/// * `observed` - When the jobs were listed, in RFC 3339 format
+///
+/// # Returns
+///
+/// `StoredJobs` describing which jobs were updated and which states changed
+pub fn store_items(
+    index: &mut BuildIndex,
+    instance: &str,
+    items: &[Item],
+    observed: &str,
+) -> Result<StoredJobs> {
+    let mut versions = Vec::new();
+    for item in items {
+        versions.push(JobVersion {
+            uid: item.metadata.uid.clone(),
+            resource_version: item.metadata.resource_version.clone(),
+            build_id: item.status.build_id.clone(),
+            state: item.status.state.clone().unwrap_or_default(),
+            content: match &item.raw {
+                Some(raw) => raw.clone(),
+                None => serde_json::to_value(item).map_err(|e| Error::parse("Prow job", e))?,
+            },
+        });
     }
+    let stored = index.store_jobs("prow", instance, &versions, observed)?;
+    let builds = items
+        .iter()
+        .filter(|item| stored.updated.contains(&item.metadata.uid))
+        .filter_map(|item| build_record(item, instance))
+        .collect::<Vec<BuildRecord>>();
+    index.upsert(&builds)?;
+    Ok(stored)
 }
 
-/// Downloads the latest artifacts from Prow for the **given build ID**
+/// Downloads the latest artifacts from Prow for the **given build**
+///
+///  # Arguments
+///
+/// * `build` - The build for which artifacts need to be downloaded
+/// * `path` - String representing the path where the artifacts and metadata are stored
+///
+/// # Returns
+///
+/// `DownloadSummary` listing the files that could not be downloaded
+pub async fn download_artifacts(build: &BuildRecord, path: &str) -> Result<DownloadSummary> {
+    fetch_artifacts(build, path, false).await
+}
+
+/// Checks the downloaded artifacts of a build and downloads corrupt, incomplete and missing ones again
+///
+/// Artifacts are checked against the size and checksum in the manifest of the build. Artifacts
+/// downloaded before the manifest existed are compared with the size reported by the server.
+/// Partially written files left behind by an interrupted download are removed.
 ///
 ///  # Arguments
 ///
-/// * `build_id` - The build ID for which artifacts need to be downloaded
+/// * `build` - The build whose artifacts need to be verified
 /// * `path` - String representing the path where the artifacts and metadata are stored
-pub async fn download_artifacts(build_id: &str, path: &str) {
-    let mut build_link: String = String::from("");
+///
+/// # Returns
+///
+/// `DownloadSummary` with the number of corrupt files that were downloaded again
+pub async fn verify_artifacts(build: &BuildRecord, path: &str) -> Result<DownloadSummary> {
+    for file in create_file_index(build.artifact_path(path)).await {
+        if file.ends_with(".part") {
+            std::fs::remove_file(&file).map_err(|e| Error::io(&file, e))?;
+        }
+    }
+    // Test results parsed from corrupt JUnit reports are parsed again, as their checksums change
+    fetch_artifacts(build, path, true).await
+}
+
+/// Lists all builds whose artifacts have been downloaded
+///
+/// # Arguments
+///
+/// * `path` - String representing the path where the artifacts and metadata are stored
+///
+/// # Returns
+///
+/// The instance and build ID of every build with an artifact folder
+pub async fn downloaded_builds(path: &str) -> Result<Vec<(String, String)>> {
+    // Moves artifacts downloaded by earlier versions into the folders of their instance
+    open_index(path).await?;
+    let prow_path = format!("{}prow", check_slash(path));
+    let mut builds = Vec::new();
+    for instance in sub_folders(&prow_path)? {
+        for build_id in sub_folders(&format!("{}/{}/artifacts", prow_path, instance))? {
+            builds.push((instance.clone(), build_id));
+        }
+    }
+    Ok(builds)
+}
+
+/// Lists the names of the folders within a folder, which may not exist
+fn sub_folders(path: &str) -> Result<Vec<String>> {
+    if !Path::new(path).is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut folders = Vec::new();
+    for entry in std::fs::read_dir(path).map_err(|e| Error::io(path, e))? {
+        let entry = entry.map_err(|e| Error::io(path, e))?;
+        if entry.path().is_dir() {
+            folders.push(entry.file_name().to_string_lossy().to_string());
+        }
+    }
+    folders.sort();
+    Ok(folders)
+}
+
+/// An artifact file to download
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct ArtifactFile {
+    pub url: String,
+    /// Path relative to the artifact folder of the build
+    pub path: String,
+    /// Size in bytes, if it was listed
+    pub size: Option<u64>,
+}
+
+/// Where the files of a build are downloaded from
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArtifactLocation {
+    /// URL of the artifact page, whose HTML listing links to the files
+    Listing(String),
+    /// Folder of a GCS bucket, read through the JSON API
+    Bucket { bucket: String, prefix: String },
+}
+
+impl ArtifactLocation {
+    /// URL of a file in the top folder of the build
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the file, e.g. `started.json`
+    pub fn file_url(&self, name: &str) -> String {
+        match self {
+            ArtifactLocation::Listing(url) => format!("{}{}", check_slash(url), name),
+            ArtifactLocation::Bucket { bucket, prefix } => {
+                gcs::object_url(bucket, &format!("{}{}", prefix, name))
+            }
+        }
+    }
+}
+
+async fn fetch_artifacts(build: &BuildRecord, path: &str, verify: bool) -> Result<DownloadSummary> {
+    if build.url.is_empty() {
+        return Err(Error::NotFound(format!(
+            "Build {} has no artifacts yet, as it is still {}",
+            build.build_id, build.state
+        )));
+    }
     let path_slash = check_slash(path);
-    let failure_files = files_in_folder(format!("{}prow/failure", path_slash).as_str()).await;
-    let success_files = files_in_folder(format!("{}prow/success", path_slash).as_str()).await;
-    let folder_artifacts = format!("{}prow/artifacts", path_slash);
-    if !Path::new(&folder_artifacts).exists() {
-        std::fs::create_dir_all(&folder_artifacts)
-            .expect(format!("Failed to create directory: {}", &folder_artifacts).as_str());
+    let artifact_path = build.artifact_path(&path_slash);
+    create_dir(&artifact_path)?;
+    let mut summary = DownloadSummary {
+        build_id: build.build_id.clone(),
+        ..Default::default()
+    };
+    let mut listed = Vec::new();
+    if let Some((bucket, build_path)) = bucket_path(build, &path_slash).await {
+        match list_bucket_artifacts(&bucket, &build_path, &artifact_path, &mut summary).await {
+            Ok(files) => listed.push((
+                ArtifactLocation::Bucket {
+                    prefix: format!("{}/", build_path.trim_end_matches('/')),
+                    bucket,
+                },
+                files,
+            )),
+            Err(e) => println!(
+                "⛔\t\x1b[93m\x1b[1mCould not list the artifacts of build {} in its bucket, reading its artifact page instead: {}\x1b[0m",
+                build.build_id, e
+            ),
+        }
+    }
+    if listed.is_empty() {
+        for url in find_urls(&build.url, "Artifacts".to_string()).await? {
+            let mut files = Vec::new();
+            list_artifacts_recursive(&url, &artifact_path, "", &mut files, &mut summary).await?;
+            listed.push((ArtifactLocation::Listing(url), files));
+        }
     }
+    for (location, files) in listed {
+        summary.merge(download_artifact_files(files, &artifact_path, verify).await?);
+        match download_build_details(&location, &artifact_path, build.clone()).await {
+            Ok(build) => open_index(&path_slash).await?.upsert(&[build])?,
+            Err(e) => println!(
+                "⛔\t\x1b[93m\x1b[1mFailed to read the details of build {}: {}\x1b[0m",
+                build.build_id, e
+            ),
+        }
+    }
+    // Builds with failed files are downloaded again by the next collection
+    let mut manifest = Manifest::load(&artifact_path)?;
+    if manifest.complete != summary.failed.is_empty() {
+        manifest.complete = summary.failed.is_empty();
+        manifest.save(&artifact_path)?;
+    }
+    summary.report();
+    Ok(summary)
+}
 
-    if success_files.is_empty() && failure_files.is_empty() {
-        println!(
-            "⛔\t\x1b[93m\x1b[1mNo data found. Please run at least one collection step first.\x1b[0m"
-        );
-    } else {
-        for file in failure_files {
-            let mut collect_file = File::open(&file).expect("Failed to open failure file");
-            let check_file: HashMap<String, [String; 2]> =
-                serde_json::from_reader(&mut collect_file)
-                    .expect("Failed to deserialize file contents");
-            build_link = match check_file.get(build_id) {
-                Some(url) => url[0].clone(),
-                None => String::from(""),
+/// Bucket and path the artifacts of a Prow job are uploaded to, following the path strategy of
+/// its GCS configuration
+///
+/// # Arguments
+///
+/// * `item` - The Prow job
+///
+/// # Returns
+///
+/// Bucket and path of the build, or `None` if the job does not upload its artifacts to GCS or has
+/// no build ID yet
+pub fn gcs_path(item: &Item) -> Option<(String, String)> {
+    let gcs = item
+        .spec
+        .decoration_config
+        .as_ref()?
+        .gcs_configuration
+        .as_ref()?;
+    let bucket = gcs.bucket.strip_prefix("gs://").unwrap_or(&gcs.bucket);
+    // Buckets of other storage providers are given with their scheme, e.g. `s3://`
+    if bucket.is_empty() || bucket.contains("://") {
+        return None;
+    }
+    let build_id = item.status.build_id.as_ref()?;
+    let job = &item.spec.job;
+    let root = match item.spec.type_field.as_str() {
+        "presubmit" => {
+            let refs = item.spec.refs.as_ref()?;
+            let pull = refs.pulls.first()?;
+            let is_default_org = gcs.default_org.as_deref() == Some(refs.org.as_str());
+            let is_default_repo = gcs.default_repo.as_deref() == Some(refs.repo.as_str());
+            let repo_path = match gcs.path_strategy.as_str() {
+                "legacy" if is_default_org && is_default_repo => String::new(),
+                "legacy" | "single" if is_default_org => refs.repo.clone(),
+                _ => format!("{}_{}", refs.org, refs.repo),
             };
+            format!("pr-logs/pull/{}/{}/{}", repo_path, pull.number, job)
         }
-        if build_link == "" {
-            for file in success_files {
-                let mut collect_file = File::open(&file).expect("Failed to open success file");
-                let check_file: HashMap<String, [String; 2]> =
-                    serde_json::from_reader(&mut collect_file)
-                        .expect("Failed to deserialize file contents");
-                build_link = match check_file.get(build_id) {
-                    Some(url) => url[0].clone(),
-                    None => String::from(""),
-                };
-            }
+        "batch" => format!("pr-logs/pull/batch/{}", job),
+        _ => format!("logs/{}", job),
+    };
+    let path = [
+        gcs.path_prefix.as_deref().unwrap_or_default(),
+        &root,
+        build_id,
+    ]
+    .iter()
+    .flat_map(|part| part.split('/'))
+    .filter(|part| !part.is_empty())
+    .collect::<Vec<&str>>()
+    .join("/");
+    Some((bucket.to_string(), path))
+}
+
+/// Reads bucket and path of a build from its URL, which links to `/view/gs/<bucket>/<path>` on
+/// Prow instances using Spyglass
+fn view_path(url: &str) -> Option<(String, String)> {
+    let (_, location) = url.split_once("/view/gs/")?;
+    let location = location.split(['?', '#']).next().unwrap_or_default();
+    split_bucket_url(&format!("gs://{}", location.trim_end_matches('/')))
+        .filter(|(_, path)| !path.is_empty())
+}
+
+/// Finds the bucket and path the artifacts of a build were uploaded to
+///
+/// The path is derived from the GCS configuration of the stored Prow job, or else read from the
+/// URL of the build.
+///
+/// # Arguments
+///
+/// * `build` - The build
+/// * `path` - The root data directory
+///
+/// # Returns
+///
+/// Bucket and path of the build, or `None` if they are unknown or GCS access is disabled
+async fn bucket_path(build: &BuildRecord, path: &str) -> Option<(String, String)> {
+    if !downloader().gcs().enabled {
+        return None;
+    }
+    let content = match open_index(path).await {
+        Ok(index) => index
+            .job("prow", &build.instance, &build.build_id)
+            .ok()
+            .flatten(),
+        Err(_) => None,
+    };
+    content
+        .and_then(|content| serde_json::from_str::<Item>(&content).ok())
+        .and_then(|item| gcs_path(&item))
+        .or_else(|| view_path(&build.url))
+}
+
+/// Lists the selected artifact files of a build in its bucket and creates their folders
+///
+/// # Arguments
+///
+/// * `bucket` - Name of the bucket
+/// * `build_path` - Path of the build within the bucket
+/// * `target_path` - Path to where the artifacts of the build are stored
+/// * `summary` - Counts the files that are not selected
+///
+/// # Returns
+///
+/// The files with their sizes, or `Error::NotFound` if the bucket has no files of the build
+async fn list_bucket_artifacts(
+    bucket: &str,
+    build_path: &str,
+    target_path: &str,
+    summary: &mut DownloadSummary,
+) -> Result<Vec<ArtifactFile>> {
+    let prefix = format!("{}/", build_path.trim_end_matches('/'));
+    println!(
+        "📁\t\x1b[32m\x1b[1mDownloading artifacts: gs://{}/{}\x1b[0m",
+        bucket, prefix
+    );
+    let objects = gcs::list_objects(bucket, &prefix).await?;
+    if objects.is_empty() {
+        return Err(Error::NotFound(format!(
+            "gs://{}/{} does not contain any files",
+            bucket, prefix
+        )));
+    }
+    let filter = downloader().filter();
+    let mut files = Vec::new();
+    for object in objects {
+        let Some(artifact_path) = object.name.strip_prefix(&prefix) else {
+            continue;
+        };
+        // Folder placeholders end with a slash, and names leaving the artifact folder are unsafe
+        if artifact_path
+            .split('/')
+            .any(|part| part.is_empty() || part == "." || part == "..")
+        {
+            continue;
         }
-        if build_link == "" {
-            println!(
-                "⛔\t\x1b[93m\x1b[1mBuild ID not found: {}\x1b[0m",
-                &build_id
-            );
+        if !filter.is_selected(artifact_path) {
+            summary.excluded += 1;
+            continue;
         }
-        if build_link != "" {
-            for url in find_urls(&build_link, "Artifacts".to_string()).await {
-                let artifact_path = format!("{}prow/artifacts/{}", &path_slash, &build_id);
-                download_artifacts_recursive(&url, &artifact_path).await;
-            }
+        if let Some(folder) = Path::new(&format!("{}/{}", target_path, artifact_path)).parent() {
+            create_dir(&folder.to_string_lossy())?;
         }
+        files.push(ArtifactFile {
+            url: gcs::object_url(bucket, &object.name),
+            path: artifact_path.to_string(),
+            size: Some(object.size),
+        });
     }
+    Ok(files)
+}
+
+/// Returns true if all artifacts of a build have been downloaded without failures before
+///
+/// # Arguments
+///
+/// * `build` - The build
+/// * `path` - The root data directory
+pub fn has_all_artifacts(build: &BuildRecord, path: &str) -> bool {
+    Manifest::load(&build.artifact_path(path)).is_ok_and(|manifest| manifest.complete)
+}
+
+/// Downloads a JSON file of a build into its artifact folder, unless it has been downloaded before
+///
+/// # Returns
+///
+/// The parsed file, or `None` if the build does not have the file
+async fn download_build_file<T: serde::de::DeserializeOwned>(
+    location: &ArtifactLocation,
+    artifact_path: &str,
+    name: &str,
+) -> Result<Option<T>> {
+    let target_file = format!("{}/{}", artifact_path, name);
+    if !Path::new(&target_file).exists() {
+        let file_url = location.file_url(name);
+        let content = match downloader().get_content(&file_url, None).await {
+            Ok(content) => content,
+            Err(Error::NotFound(_)) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let entry = save_artifact(&target_file, &file_url, &content)?;
+        let mut manifest = Manifest::load(artifact_path)?;
+        manifest.files.insert(name.to_string(), entry);
+        manifest.save(artifact_path)?;
+    }
+    read_json(&target_file).map(Some)
+}
+
+/// Reads `prowjob.json`, `started.json` and `finished.json` of a build and adds their details to its record
+///
+/// The record is replaced with the one created from `prowjob.json` if the file exists, so that
+/// builds which are no longer listed by Prow keep complete metadata.
+///
+/// # Arguments
+///
+/// * `location` - Where the files of the build are downloaded from
+/// * `artifact_path` - Path to where the artifacts of the build are stored
+/// * `build` - The currently known record of the build
+///
+/// # Returns
+///
+/// The updated `BuildRecord`
+pub async fn download_build_details(
+    location: &ArtifactLocation,
+    artifact_path: &str,
+    build: BuildRecord,
+) -> Result<BuildRecord> {
+    create_dir(artifact_path)?;
+    // The Prow job is only used to complete the record, so a job that cannot be parsed is skipped
+    let prowjob: Option<Item> = download_build_file(location, artifact_path, "prowjob.json")
+        .await
+        .unwrap_or_else(|e| {
+            println!("Skipping prowjob.json of build {}: {}", build.build_id, e);
+            None
+        });
+    let started: Option<Started> =
+        download_build_file(location, artifact_path, "started.json").await?;
+    let finished: Option<Finished> =
+        download_build_file(location, artifact_path, "finished.json").await?;
+
+    let mut build = match prowjob
+        .as_ref()
+        .and_then(|prowjob| build_record(prowjob, &build.instance))
+    {
+        Some(record) if record.build_id == build.build_id => BuildRecord {
+            duration: build.duration,
+            result: build.result,
+            revision: build.revision,
+            node: build.node,
+            ..record
+        },
+        _ => build,
+    };
+    if let Some(started) = &started {
+        build.node = started.node.clone().or(build.node);
+        build.revision = started.repo_version.clone().or(build.revision);
+    }
+    if let Some(finished) = &finished {
+        build.result = finished.result.clone().or(build.result);
+        build.revision = finished.revision.clone().or(build.revision);
+    }
+    if let (Some(start), Some(end)) = (
+        started.as_ref().and_then(|started| started.timestamp),
+        finished.as_ref().and_then(|finished| finished.timestamp),
+    ) {
+        build.duration = Some(end - start);
+    }
+    Ok(build)
 }
 
 /// Recursively downloads all artifacts for a **given url into the given path**
 ///
+/// Directory listings are read first, then all files that have not been downloaded before are
+/// downloaded concurrently. Only artifacts selected by the include and exclude patterns of the
+/// download settings are downloaded, and if size limits are set, the size of each file is
+/// requested before it is downloaded. Files are written atomically and recorded in the manifest
+/// of the build. Files and folders that cannot be downloaded are skipped and reported in the
+/// summary.
+///
 ///  # Arguments
 ///
 /// * `url` - The initial URL from where artifacts should be downloaded
 /// * `target_path` - Path to where the artifacts are stored, mirroring the existing artifact folder structure
-#[async_recursion]
-pub async fn download_artifacts_recursive(url: &str, target_path: &str) {
-    let base_url_split = url.split("/");
-    let base_url = base_url_split.collect::<Vec<&str>>()[0..3].join("/");
+/// * `verify` - If set to true, files that have been downloaded before are checked as well and downloaded again if they are corrupt or incomplete
+///
+/// # Returns
+///
+/// `DownloadSummary` listing the files that were skipped or could not be downloaded
+pub async fn download_artifacts_recursive(
+    url: &str,
+    target_path: &str,
+    verify: bool,
+) -> Result<DownloadSummary> {
+    let mut summary = DownloadSummary::default();
+    let mut files = Vec::new();
+    list_artifacts_recursive(url, target_path, "", &mut files, &mut summary).await?;
+    summary.merge(download_artifact_files(files, target_path, verify).await?);
+    Ok(summary)
+}
+
+/// Downloads listed artifacts into the given path, unless they have been downloaded before
+///
+/// Files are downloaded concurrently. If size limits are set, the size of each file whose size
+/// was not listed is requested before it is downloaded, and files whose size is still unknown
+/// are downloaded last, one at a time. Files are written atomically and recorded in the manifest
+/// of the build.
+///
+///  # Arguments
+///
+/// * `files` - The selected artifact files, with paths relative to `target_path`
+/// * `target_path` - Path to where the artifacts are stored
+/// * `verify` - If set to true, files that have been downloaded before are checked as well and downloaded again if they are corrupt or incomplete
+///
+/// # Returns
+///
+/// `DownloadSummary` listing the files that were skipped or could not be downloaded
+async fn download_artifact_files(
+    files: Vec<ArtifactFile>,
+    target_path: &str,
+    verify: bool,
+) -> Result<DownloadSummary> {
+    let filter = downloader().filter();
+    let mut summary = DownloadSummary::default();
+    let mut manifest = Manifest::load(target_path)?;
+    let manifest_files = manifest.files.len();
+
+    // Sizes are needed to enforce the size limits and to check files that are not in the manifest
+    let mut sizes = files
+        .iter()
+        .filter_map(|file| Some((file.url.clone(), file.size?)))
+        .collect::<HashMap<String, u64>>();
+    let mut existing = Vec::new();
+    let mut unrecorded = Vec::new();
+    let mut pending = Vec::new();
+    for ArtifactFile { url, path, .. } in files {
+        if !Path::new(&format!("{}/{}", target_path, path)).exists() {
+            pending.push((url, path));
+            continue;
+        }
+        match verify.then(|| manifest.verify(target_path, &path)) {
+            Some(Some(false)) => {
+                summary.corrupt += 1;
+                pending.push((url, path));
+            }
+            Some(None) => unrecorded.push((url, path)),
+            _ => existing.push(path),
+        }
+    }
+
+    let mut requests = tokio::task::JoinSet::new();
+    let limited = filter.max_file_size.is_some() || filter.max_build_size.is_some();
+    for (url, _) in pending
+        .iter()
+        .filter(|_| limited)
+        .chain(unrecorded.iter())
+        .filter(|(url, _)| !sizes.contains_key(url))
+    {
+        let url = url.clone();
+        requests.spawn(async move {
+            // Servers that do not answer HEAD requests are treated as not reporting the size
+            let size = downloader().size(&url).await.ok().flatten();
+            (url, size)
+        });
+    }
+    while let Some(request) = requests.join_next().await {
+        if let Ok((url, Some(size))) = request {
+            sizes.insert(url, size);
+        }
+    }
+
+    for (url, path) in unrecorded {
+        let target_file = format!("{}/{}", target_path, path);
+        let content = std::fs::read(&target_file).map_err(|e| Error::io(&target_file, e))?;
+        match sizes.get(&url) {
+            Some(&size) if size != content.len() as u64 => {
+                summary.corrupt += 1;
+                pending.push((url, path));
+            }
+            Some(&size) => {
+                manifest.files.insert(
+                    path.clone(),
+                    ManifestEntry {
+                        url,
+                        size,
+                        etag: None,
+                        sha256: sha256(&content),
+                    },
+                );
+                existing.push(path);
+            }
+            None => {
+                summary.unverified += 1;
+                existing.push(path);
+            }
+        }
+    }
+
+    // Files downloaded before count towards the size limit of the build as well
+    let mut remaining = filter.max_build_size;
+    for path in existing {
+        summary.existing += 1;
+        if let Ok(metadata) = std::fs::metadata(format!("{}/{}", target_path, path)) {
+            remaining = remaining.map(|remaining| remaining.saturating_sub(metadata.len()));
+        }
+    }
 
-    for artifact_url in find_urls(&url, "".to_string()).await {
+    // Files of unknown size are downloaded one after another once the others are done, each
+    // limited to the rest of the budget, which is then reduced by the size actually received
+    let mut unknown_size = Vec::new();
+    let mut downloads = tokio::task::JoinSet::new();
+    for (url, path) in pending {
+        let size = sizes.get(&url).copied();
+        if let (Some(size), Some(max_file_size)) = (size, filter.max_file_size) {
+            if size > max_file_size {
+                summary.skipped.push(SkippedFile {
+                    url,
+                    size: Some(size),
+                    reason: format!(
+                        "{} bytes exceed the maximum file size of {} bytes",
+                        size, max_file_size
+                    ),
+                });
+                continue;
+            }
+        }
+        match (remaining.as_mut(), size) {
+            (Some(_), None) => {
+                unknown_size.push((url, path));
+                continue;
+            }
+            (Some(remaining), Some(size)) if size > *remaining => {
+                summary.skipped.push(SkippedFile {
+                    url,
+                    size: Some(size),
+                    reason: "the maximum size of the build has been reached".to_string(),
+                });
+                continue;
+            }
+            (Some(remaining), Some(size)) => *remaining -= size,
+            (None, _) => {}
+        }
+        let target_file = format!("{}/{}", target_path, path);
+        downloads.spawn(download_artifact(
+            url,
+            path,
+            target_file,
+            filter.max_file_size,
+        ));
+    }
+    let mut changed = manifest.files.len() != manifest_files;
+    while let Some(download) = downloads.join_next().await {
+        match download {
+            Ok((url, path, result)) => {
+                // The budget reserved for a file that was not downloaded is given back
+                if let (Err(_), Some(remaining), Some(size)) =
+                    (&result, remaining.as_mut(), sizes.get(&url))
+                {
+                    *remaining += size;
+                }
+                changed |= record_download(&mut summary, &mut manifest, url, path, result);
+            }
+            Err(e) => summary.failed.push(FailedFile {
+                url: target_path.to_string(),
+                error: e.to_string(),
+            }),
+        }
+    }
+    for (url, path) in unknown_size {
+        let budget = remaining.unwrap_or_default();
+        if budget == 0 {
+            summary.skipped.push(SkippedFile {
+                url,
+                size: None,
+                reason: "the maximum size of the build has been reached".to_string(),
+            });
+            continue;
+        }
+        let limit = filter
+            .max_file_size
+            .map_or(budget, |limit| limit.min(budget));
+        let target_file = format!("{}/{}", target_path, path);
+        let (url, path, result) = download_artifact(url, path, target_file, Some(limit)).await;
+        if let (Ok(entry), Some(remaining)) = (&result, remaining.as_mut()) {
+            *remaining = remaining.saturating_sub(entry.size);
+        }
+        changed |= record_download(&mut summary, &mut manifest, url, path, result);
+    }
+    if changed {
+        manifest.save(target_path)?;
+    }
+    Ok(summary)
+}
+
+/// Downloads a single artifact and writes it to its target file
+///
+/// # Arguments
+///
+/// * `url` - URL of the artifact
+/// * `path` - Path of the artifact relative to the artifact folder of the build
+/// * `target_file` - Path the artifact is written to
+/// * `limit` - Maximum number of bytes to download
+///
+/// # Returns
+///
+/// URL and path of the artifact together with its `ManifestEntry`, or `Error::InvalidInput` if
+/// it is larger than `limit`
+async fn download_artifact(
+    url: String,
+    path: String,
+    target_file: String,
+    limit: Option<u64>,
+) -> (String, String, Result<ManifestEntry>) {
+    let result = match downloader().get_content(&url, limit).await {
+        Ok(content) => save_artifact(&target_file, &url, &content),
+        Err(e) => Err(e),
+    };
+    (url, path, result)
+}
+
+/// Adds the outcome of a download to the summary and the manifest of the build
+///
+/// Files that turned out to be larger than their limit are reported as skipped rather than
+/// failed, so that they do not keep the build from being complete.
+///
+/// # Returns
+///
+/// True if the manifest has changed
+fn record_download(
+    summary: &mut DownloadSummary,
+    manifest: &mut Manifest,
+    url: String,
+    path: String,
+    result: Result<ManifestEntry>,
+) -> bool {
+    match result {
+        Ok(entry) => {
+            summary.downloaded += 1;
+            manifest.files.insert(path, entry);
+            true
+        }
+        Err(Error::InvalidInput(reason)) => {
+            summary.skipped.push(SkippedFile {
+                url,
+                size: None,
+                reason,
+            });
+            false
+        }
+        Err(e) => {
+            summary.failed.push(FailedFile {
+                url,
+                error: e.to_string(),
+            });
+            false
+        }
+    }
+}
+
+/// Recursively lists the selected artifact files below a URL and creates their folders
+///
+/// Files are listed with their URL and their path relative to the artifact folder of the build.
+/// Only a failure to list `url` itself is returned as an error, failures to list sub folders
+/// are added to the summary.
+#[async_recursion]
+async fn list_artifacts_recursive(
+    url: &str,
+    target_path: &str,
+    relative_path: &str,
+    files: &mut Vec<ArtifactFile>,
+    summary: &mut DownloadSummary,
+) -> Result<()> {
+    let base_url = url.split("/").take(3).collect::<Vec<&str>>().join("/");
+    let filter = downloader().filter();
+
+    for artifact_url in find_urls(url, "".to_string()).await? {
         let mut absolute_artifact_url = artifact_url.to_string();
         if artifact_url.starts_with("/") {
             absolute_artifact_url = format!("{}{}", &base_url, &artifact_url);
         }
-        if &absolute_artifact_url.len() < &url.len() {
+        if absolute_artifact_url.len() < url.len() {
             continue;
         }
-        let artifact_name = &artifact_url
-            .rsplit_terminator("/")
-            .next()
-            .unwrap()
-            .to_string();
+        let Some(artifact_name) = artifact_url.rsplit_terminator("/").next() else {
+            continue;
+        };
         if artifact_name.eq("..") || artifact_name.eq(".") {
             continue;
         }
-        if !Path::new(&target_path).exists() {
-            std::fs::create_dir_all(&target_path)
-                .expect(format!("Failed to create directory: {}", &target_path).as_str());
-        }
+        let artifact_path = format!("{}{}", relative_path, artifact_name);
         if artifact_url.ends_with("/") {
+            if filter.is_skipped_folder(&artifact_path) {
+                continue;
+            }
             println!(
                 "📁\t\x1b[32m\x1b[1mDownloading artifact: {}\x1b[0m",
                 &absolute_artifact_url
             );
-            download_artifacts_recursive(
+            let folder_path = format!("{}/{}", &target_path, &artifact_name);
+            if let Err(e) = list_artifacts_recursive(
                 &absolute_artifact_url,
-                format!("{}/{}", &target_path, &artifact_name).as_str(),
+                &folder_path,
+                &format!("{}/", artifact_path),
+                files,
+                summary,
             )
-            .await;
-        } else {
-            let target_file = format!("{}/{}", &target_path, &artifact_name);
-            if !Path::new(&target_file).exists() {
-                let resp = reqwest::get(&absolute_artifact_url)
-                    .await
-                    .expect("Failed to get artifact")
-                    .bytes()
-                    .await
-                    .expect("Failed to get artifact contents");
-                File::create(&target_file).expect("Failed to create artifact file");
-                std::fs::write(&target_file, resp.as_ref()).expect("Failed to write artifact file");
+            .await
+            {
+                summary.failed.push(FailedFile {
+                    url: absolute_artifact_url,
+                    error: e.to_string(),
+                });
             }
+        } else if filter.is_selected(&artifact_path) {
+            create_dir(target_path)?;
+            files.push(ArtifactFile {
+                url: absolute_artifact_url,
+                path: artifact_path,
+                size: None,
+            });
+        } else {
+            summary.excluded += 1;
         }
     }
+    Ok(())
 }
 
 /// Find URLs in a text using a regular expression searching for HTML tags
@@ -935,115 +2035,566 @@ pub async fn download_artifacts_recursive(url: &str, target_path: &str) {
 /// # Returns
 ///
 /// A vector of URLs found in the text
-pub async fn find_urls(url: &str, aname: String) -> Vec<String> {
-    let resp = reqwest::get(url)
-        .await
-        .expect("Failed to get HTML from given URL")
-        .text()
-        .await
-        .expect("Failed to convert HTML to text");
+pub async fn find_urls(url: &str, aname: String) -> Result<Vec<String>> {
+    let resp = downloader().get_text(url).await?;
 
-    if &aname == "" {
+    if aname.is_empty() {
         let mut urls = Vec::new();
         let rx = Regex::new("<a .*?href=\"([^\"]*)\">(.*?)</a>").unwrap();
         for cap in rx.captures_iter(&resp) {
             urls.push(cap[1].to_string());
         }
 
-        return urls;
+        Ok(urls)
     } else {
-        let aname_str = &aname.as_str();
-        let rx =
-            Regex::new(format!("<a .*?href=\"([^\"]*)\">(.*?){}</a>", aname_str).as_str()).unwrap();
-        let captures = rx.captures(&resp).unwrap();
-        return vec![captures[1].to_string()];
+        let rx = Regex::new(
+            format!("<a .*?href=\"([^\"]*)\">(.*?){}</a>", regex::escape(&aname)).as_str(),
+        )
+        .unwrap();
+        match rx.captures(&resp) {
+            Some(captures) => Ok(vec![captures[1].to_string()]),
+            None => Err(Error::NotFound(format!(
+                "No \"{}\" link found on {}",
+                aname, url
+            ))),
+        }
     }
 }
 
+/// Creates the index record of a Prow job in any state
+///
+/// # Arguments
+///
+/// * `item` - The Prow job metadata
+/// * `instance` - Name of the Prow instance that listed the job
+///
+/// # Returns
+///
+/// `BuildRecord` of the job, or `None` if the job has no build ID yet, where the URL is left empty
+/// for jobs that have not been scheduled yet
+pub fn build_record(item: &Item, instance: &str) -> Option<BuildRecord> {
+    let build_id = item.status.build_id.as_ref()?;
+    Some(BuildRecord {
+        build_id: build_id.to_string(),
+        url: item.status.url.clone().unwrap_or_default(),
+        job: item.metadata.labels.prow_k8s_io_job.to_string(),
+        state: item.status.state.clone().unwrap_or_default(),
+        start_time: item.status.start_time.clone(),
+        completion_time: item.status.completion_time.clone(),
+        refs: item
+            .spec
+            .refs
+            .as_ref()
+            .and_then(|refs| serde_json::to_value(refs).ok()),
+        source: "prow".to_string(),
+        instance: instance.to_string(),
+        ..Default::default()
+    })
+}
+
+/// Opens the build index, importing Prow metadata snapshots stored by earlier versions if it has just been created
+///
+/// Artifacts and labels stored by versions that did not separate Prow instances are moved into
+/// the folders of their instance when the index is upgraded.
+///
+/// # Arguments
+///
+/// * `volume` - The root data directory
+///
+/// # Returns
+///
+/// The opened `BuildIndex`
+pub async fn open_index(volume: &str) -> Result<BuildIndex> {
+    let mut index = BuildIndex::open(volume)?;
+    if index.previous_version() >= 4 {
+        return Ok(index);
+    }
+    let folder_data = format!("{}prow", check_slash(volume));
+    if !Path::new(&folder_data).exists() {
+        return Ok(index);
+    }
+    let mut snapshots = files_in_folder(&folder_data)
+        .await?
+        .into_iter()
+        .filter(|file| file.contains("/collect-") && file.ends_with(".json"))
+        .collect::<Vec<String>>();
+    snapshots.sort();
+    if !index.is_created() {
+        snapshots.clear();
+    }
+    if !snapshots.is_empty() {
+        println!(
+            "🔍\t\x1b[32m\x1b[1mIndexing {} stored Prow snapshots...\x1b[0m",
+            snapshots.len()
+        );
+    }
+    for snapshot in snapshots {
+        // Snapshots are named after the time they were collected, e.g. collect-2023-11-02-10-15-00.json
+        let time_id = snapshot
+            .rsplit_once("/collect-")
+            .map(|(_, name)| name.trim_end_matches(".json"))
+            .unwrap_or_default();
+        let observed = chrono::NaiveDateTime::parse_from_str(time_id, "%Y-%m-%d-%H-%M-%S")
+            .map(|time| chrono::TimeZone::from_utc_datetime(&chrono::Utc, &time).to_rfc3339())
+            .unwrap_or(time_id.to_string());
+        let items = std::fs::read(&snapshot)
+            .map_err(|e| Error::io(&snapshot, e))
+            .and_then(|content| parse_items(&content, &snapshot));
+        match items {
+            Ok(items) => {
+                // Snapshots only contain the jobs of a single instance, named after its host
+                let instance = items
+                    .iter()
+                    .find_map(|item| item.status.url.as_deref())
+                    .map(instance_name)
+                    .unwrap_or_default();
+                store_items(&mut index, &instance, &items, &observed)?;
+            }
+            Err(e) => println!("⛔\t\x1b[93m\x1b[1mSkipping snapshot: {}\x1b[0m", e),
+        }
+    }
+    move_legacy_artifacts(&index, volume)?;
+    move_legacy_labels(&index, volume)?;
+    Ok(index)
+}
+
+/// Moves the artifact folders and test results of builds from `prow/artifacts` and `tests` into
+/// the folders of their instance
+fn move_legacy_artifacts(index: &BuildIndex, volume: &str) -> Result<()> {
+    let legacy_path = format!("{}prow/artifacts", check_slash(volume));
+    for build_id in sub_folders(&legacy_path)? {
+        let builds = index.find(&build_id)?;
+        let [build] = builds.as_slice() else {
+            println!(
+                "⛔\t\x1b[93m\x1b[1mKeeping the artifacts of build {} in {}, as its instance is unknown\x1b[0m",
+                build_id, legacy_path
+            );
+            continue;
+        };
+        let artifact_path = build.artifact_path(volume);
+        if let Some(parent) = Path::new(&artifact_path).parent() {
+            create_dir(&parent.to_string_lossy())?;
+        }
+        let source_path = format!("{}/{}", legacy_path, build_id);
+        std::fs::rename(&source_path, &artifact_path).map_err(|e| Error::io(&source_path, e))?;
+        let legacy_results = format!("{}tests/{}.json", check_slash(volume), build_id);
+        if Path::new(&legacy_results).exists() {
+            let test_results = test_results_path(build, volume);
+            if let Some(parent) = Path::new(&test_results).parent() {
+                create_dir(&parent.to_string_lossy())?;
+            }
+            std::fs::rename(&legacy_results, &test_results)
+                .map_err(|e| Error::io(&legacy_results, e))?;
+        }
+    }
+    // Only succeeds once all builds have been moved
+    let _ = std::fs::remove_dir(&legacy_path);
+    Ok(())
+}
+
+/// Looks up a build in the build index
+///
+/// # Arguments
+///
+/// * `build_id` - Requested build ID
+/// * `instance` - The Prow instance the build was collected from, only needed if several instances have a build with this ID
+/// * `source_path` - Root path to where the build data is stored
+///
+/// # Returns
+///
+/// `BuildRecord` of the build, if it has been collected before, or `Error::InvalidInput` if no
+/// instance was given and several instances have a build with this ID
+pub async fn find_build(
+    build_id: &str,
+    instance: Option<&str>,
+    source_path: &str,
+) -> Result<Option<BuildRecord>> {
+    let index = open_index(source_path).await?;
+    if let Some(instance) = instance {
+        return index.get(instance, build_id);
+    }
+    let mut builds = index.find(build_id)?;
+    if builds.len() > 1 {
+        return Err(Error::InvalidInput(format!(
+            "Build {} has been collected from several instances, please specify one of: {}",
+            build_id,
+            builds
+                .iter()
+                .map(|build| format!("{}/{}", build.instance, build.build_id))
+                .collect::<Vec<String>>()
+                .join(", ")
+        )));
+    }
+    Ok(builds.pop())
+}
+
+/// Lists the indexed builds, the most recently started last
+///
+/// # Arguments
+///
+/// * `source_path` - Root path to where the build data is stored
+/// * `days` - If set, only builds started within this number of days before now are listed
+/// * `states` - If not empty, only builds in one of these states are listed
+///
+/// # Returns
+///
+/// `BuildRecord` of every matching build
+pub async fn list_builds(
+    source_path: &str,
+    days: Option<u32>,
+    states: &[BuildState],
+) -> Result<Vec<BuildRecord>> {
+    let since = days.map(|days| {
+        (chrono::Utc::now() - chrono::Duration::days(days as i64))
+            .format("%Y-%m-%dT%H:%M:%SZ")
+            .to_string()
+    });
+    open_index(source_path)
+        .await?
+        .list(since.as_deref(), states)
+}
+
 /// Get build information for a given build ID
 ///
 /// # Arguments
 ///
 /// * `build_id` - Request build ID
+/// * `instance` - The Prow instance the build was collected from, only needed if several instances have a build with this ID
 /// * `source_path` - Root path to where the build data is stored
+/// * `config` - The Arcalog configuration
 ///
 /// # Returns
 ///
 /// `BuildInfo` - Struct containing the build information
-pub async fn get_build_info(build_id: String, source_path: String) -> BuildInfo {
-    if build_id == "" {
-        return BuildInfo {
-            build_id: build_id,
-            build_url: None,
-            label: None,
-            state: None,
-            job_type: None,
-            events: None,
-            error: Some("Have you forgotten to submit a build ID?".to_string()),
+pub async fn get_build_info(
+    build_id: String,
+    instance: Option<String>,
+    source_path: String,
+    config: &Config,
+) -> Result<BuildInfo> {
+    if build_id.is_empty() {
+        return Err(Error::InvalidInput(
+            "Have you forgotten to submit a build ID?".to_string(),
+        ));
+    }
+    if let Some(instance) = &instance {
+        check_instance(instance)?;
+    }
+    let Some(build) = find_build(&build_id, instance.as_deref(), &source_path).await? else {
+        return Err(Error::NotFound(
+            "Please put in a valid build ID. Have you made sure to collect the metadata?"
+                .to_string(),
+        ));
+    };
+    let state = match &build.state {
+        BuildState::Failure => "⛔ failure".to_string(),
+        BuildState::Success => "✅ success".to_string(),
+        BuildState::Error => "💥 error".to_string(),
+        BuildState::Aborted => "🛑 aborted".to_string(),
+        state if !state.is_finished() => format!("⏳ {}", state),
+        state => state.to_string(),
+    };
+    // The artifacts of builds that are still running are incomplete, so they are not downloaded yet
+    let (all_events, failing_tests) = match build.state.is_finished() {
+        true => (
+            collect_events(&build, &source_path, &config.identification).await?,
+            collect_test_results(&build, &source_path)
+                .await?
+                .iter()
+                .flat_map(|suite| suite.cases.iter())
+                .filter(|case| case.is_failed())
+                .map(|case| case.full_name())
+                .collect::<Vec<String>>(),
+        ),
+        false => (Vec::new(), Vec::new()),
+    };
+    let templates = mine_templates(&all_events, &config.identification.templates);
+    // Infrastructure errors are classified like failures, as they are usually caused by the environment
+    let (classification, prediction) = match build.state.is_failed() {
+        true => (
+            Some(
+                config
+                    .analysis
+                    .rules()?
+                    .classify(&build.job, &build.state, &all_events),
+            ),
+            Classifier::load(&source_path)?.and_then(|model| model.predict(&all_events)),
+        ),
+        false => (None, None),
+    };
+    let human_label = load_label(&source_path, &build.instance, &build_id)?;
+    let transitions = open_index(&source_path)
+        .await?
+        .transitions(&build.instance, &build_id)?;
+    // Fired rules take precedence over the prediction of the classifier
+    let label = match (&classification, &prediction) {
+        _ if human_label.is_some() => human_label.as_ref().map(|human| human.label.to_str()),
+        (Some(classification), _) if !classification.rules.is_empty() => {
+            Some(classification.label.to_str())
+        }
+        (_, Some(prediction)) => Some(format!(
+            "{} ({:.0}%)",
+            prediction.label.to_str(),
+            prediction.confidence * 100.0
+        )),
+        (Some(_), None) => Some(crate::analysis::Labels::Unknown.to_str()),
+        (None, None) => None,
+    };
+    Ok(BuildInfo {
+        build_id,
+        instance: Some(build.instance),
+        build_url: Some(build.url),
+        label,
+        state: Some(state),
+        build_state: Some(build.state),
+        job_type: Some(build.job),
+        events: Some(all_events),
+        failing_tests: Some(failing_tests),
+        templates: Some(templates),
+        rules: classification.map(|classification| classification.rules),
+        prediction,
+        human_label,
+        duration: build.duration,
+        transitions: Some(transitions),
+        result: build.result,
+        revision: build.revision,
+        node: build.node,
+        error: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    /// Checks that duration, result, revision and node are read from the stored build files
+    async fn test_download_build_details() {
+        let artifact_path = std::env::temp_dir()
+            .join(format!("arcalog-build-details-{}", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        create_dir(&artifact_path).unwrap();
+        std::fs::write(
+            format!("{}/started.json", artifact_path),
+            r#"{"timestamp": 1700000000, "node": "node-1", "repo-version": "abc"}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            format!("{}/finished.json", artifact_path),
+            r#"{"timestamp": 1700000600, "passed": false, "result": "FAILURE", "revision": "def"}"#,
+        )
+        .unwrap();
+        std::fs::write(format!("{}/prowjob.json", artifact_path), "{}").unwrap();
+        let build = BuildRecord {
+            build_id: "1".to_string(),
+            ..Default::default()
         };
-    } else {
-        let mut send_build_info = BuildInfo {
-            build_id: build_id.clone(),
-            build_url: None,
-            label: None,
-            state: None,
-            job_type: None,
-            events: None,
-            error: None,
+        let location = ArtifactLocation::Listing("http://127.0.0.1:9/".to_string());
+        let build = download_build_details(&location, &artifact_path, build)
+            .await
+            .unwrap();
+        std::fs::remove_dir_all(&artifact_path).unwrap();
+        assert_eq!(build.duration, Some(600));
+        assert_eq!(build.result.as_deref(), Some("FAILURE"));
+        assert_eq!(build.revision.as_deref(), Some("def"));
+        assert_eq!(build.node.as_deref(), Some("node-1"));
+    }
+
+    #[test]
+    /// Checks that jobs with fields of other Prow configurations are parsed, unknown fields are kept
+    /// and jobs that cannot be parsed are skipped
+    fn test_parse_items() {
+        let content = r#"{"items": [
+            {
+                "metadata": {"name": "a", "uid": "1", "resourceVersion": "7", "finalizers": ["x"]},
+                "spec": {
+                    "type": "periodic",
+                    "job": "periodic-ci-openshift-release",
+                    "pod_spec": {
+                        "containers": [{
+                            "name": "test",
+                            "resources": {"limits": {"cpu": "2"}},
+                            "securityContext": {"runAsNonRoot": true}
+                        }],
+                        "nodeSelector": {"kubernetes.io/arch": "amd64"},
+                        "tolerations": [{"key": "ci", "operator": "Exists", "effect": "NoSchedule"}]
+                    },
+                    "decoration_config": {"gcs_configuration": {"bucket": "origin-ci-test", "path_strategy": "single"}},
+                    "hidden": true
+                },
+                "status": {"state": "success", "build_id": "1"}
+            },
+            {"metadata": {"name": "b"}, "spec": {"job": "broken"}}
+        ]}"#;
+        let items = parse_items(content.as_bytes(), "prowjobs.js").unwrap();
+        assert_eq!(items.len(), 1);
+        let item = &items[0];
+        assert_eq!(item.spec.extra["hidden"], serde_json::json!(true));
+        assert_eq!(item.metadata.extra["finalizers"], serde_json::json!(["x"]));
+        let pod_spec = item.spec.pod_spec.as_ref().unwrap();
+        assert_eq!(pod_spec.tolerations[0].value, None);
+        assert_eq!(pod_spec.node_selector.as_ref().unwrap().highcpu, None);
+        let content = serde_json::to_value(item).unwrap();
+        assert_eq!(content["spec"]["hidden"], serde_json::json!(true));
+        assert_eq!(
+            content["spec"]["pod_spec"]["nodeSelector"]["kubernetes.io/arch"],
+            serde_json::json!("amd64")
+        );
+        // Stored jobs keep the fields of structs that do not collect unknown fields, too
+        let mut index = BuildIndex::open_in_memory().unwrap();
+        store_items(&mut index, "prow.example.com", &items, "t1").unwrap();
+        let stored: serde_json::Value =
+            serde_json::from_str(&index.job("prow", "prow.example.com", "1").unwrap().unwrap())
+                .unwrap();
+        assert_eq!(
+            stored["spec"]["pod_spec"]["containers"][0]["securityContext"]["runAsNonRoot"],
+            serde_json::json!(true)
+        );
+        assert!(parse_items(b"<html></html>", "prowjobs.js").is_err());
+    }
+
+    #[test]
+    /// Checks that jobs are selected by name, type, repository, state and age
+    fn test_job_filter() {
+        let item = |job: &str, job_type: &str, org: &str, state: &str, start: &str| Item {
+            spec: Spec {
+                job: job.to_string(),
+                type_field: job_type.to_string(),
+                extra_refs: vec![ExtraRef {
+                    org: org.to_string(),
+                    repo: "test-infra".to_string(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            status: Status {
+                state: Some(BuildState::from(state)),
+                start_time: Some(start.to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
         };
-        let index_failure = create_file_index(format!("{}/prow/failure", source_path)).await;
-        let index_success = create_file_index(format!("{}/prow/success", source_path)).await;
-        for filename in index_failure {
-            let mut open_file = File::open(&filename).expect("Failed to open file");
-            let file_contents: HashMap<String, Vec<String>> =
-                serde_json::from_reader(&mut open_file)
-                    .expect("Failed to deserialize file contents");
-            if file_contents.contains_key(&build_id) {
-                let build_info = file_contents.get(&build_id).unwrap();
-                send_build_info.build_url = Some(build_info[0].to_string());
-                send_build_info.label = Some("❓ unknown".to_string());
-                send_build_info.state = Some("⛔ failure".to_string());
-                send_build_info.job_type = Some(build_info[1].to_string());
-                break;
-            }
-        }
-        if send_build_info.build_url.is_none() {
-            for filename in index_success {
-                let mut open_file = File::open(&filename).expect("Failed to open file");
-                let file_contents: HashMap<String, Vec<String>> =
-                    serde_json::from_reader(&mut open_file)
-                        .expect("Failed to deserialize file contents");
-                if file_contents.contains_key(&build_id) {
-                    let build_info = file_contents.get(&build_id).unwrap();
-                    send_build_info.build_url = Some(build_info[0].to_string());
-                    send_build_info.label = Some("❓ unknown".to_string());
-                    send_build_info.state = Some("✅ success".to_string());
-                    send_build_info.job_type = Some(build_info[1].to_string());
-                    break;
-                }
-            }
+        let now = chrono::DateTime::parse_from_rfc3339("2024-01-10T00:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        let recent = "2024-01-09T00:00:00Z";
+        assert!(JobFilter::default().is_selected(&item("any", "", "", "", ""), now));
+
+        let filter = JobFilter::new(&ProwParameters {
+            jobs: vec!["ci-kubernetes-e2e-.*".to_string()],
+            types: vec!["periodic".to_string()],
+            repos: vec!["kubernetes/test-infra".to_string()],
+            states: vec![BuildState::Failure],
+            max_age_days: Some(7),
+        })
+        .unwrap();
+        let selected = item(
+            "ci-kubernetes-e2e-gce",
+            "periodic",
+            "kubernetes",
+            "failure",
+            recent,
+        );
+        assert!(filter.is_selected(&selected, now));
+        for rejected in [
+            item(
+                "pull-kubernetes-e2e-gce",
+                "periodic",
+                "kubernetes",
+                "failure",
+                recent,
+            ),
+            item(
+                "ci-kubernetes-e2e-gce",
+                "presubmit",
+                "kubernetes",
+                "failure",
+                recent,
+            ),
+            item(
+                "ci-kubernetes-e2e-gce",
+                "periodic",
+                "openshift",
+                "failure",
+                recent,
+            ),
+            item(
+                "ci-kubernetes-e2e-gce",
+                "periodic",
+                "kubernetes",
+                "success",
+                recent,
+            ),
+            item(
+                "ci-kubernetes-e2e-gce",
+                "periodic",
+                "kubernetes",
+                "failure",
+                "2024-01-01T00:00:00Z",
+            ),
+        ] {
+            assert!(!filter.is_selected(&rejected, now), "{}", rejected.spec.job);
         }
+        assert!(JobFilter::new(&ProwParameters {
+            jobs: vec!["(".to_string()],
+            ..Default::default()
+        })
+        .is_err());
+    }
 
-        if send_build_info.build_url.is_some() {
-            let all_events = collect_events(build_id.clone(), source_path).await;
-            send_build_info.events = Some(all_events);
-            return send_build_info;
-        } else {
-            return BuildInfo {
-                build_id: build_id.to_string(),
-                build_url: None,
-                label: None,
-                state: None,
-                job_type: None,
-                events: None,
-                error: Some(
-                    "Please put in a valid build ID. Have you made sure to collect the metadata?"
-                        .to_string(),
-                ),
-            };
-        }
+    #[test]
+    /// Checks that the bucket paths of builds follow the path strategy of their GCS configuration
+    fn test_gcs_path() {
+        let mut item: Item = serde_json::from_value(serde_json::json!({
+            "metadata": {"uid": "1", "resourceVersion": "1"},
+            "spec": {
+                "type": "presubmit",
+                "job": "pull-kubernetes-unit",
+                "refs": {"org": "kubernetes", "repo": "kubernetes", "pulls": [{"number": 42}]},
+                "decoration_config": {"gcs_configuration": {
+                    "bucket": "gs://kubernetes-jenkins",
+                    "path_strategy": "legacy",
+                    "default_org": "kubernetes",
+                    "default_repo": "kubernetes"
+                }}
+            },
+            "status": {"build_id": "7"}
+        }))
+        .unwrap();
+        let path =
+            |item: &Item| gcs_path(item).map(|(bucket, path)| format!("{}/{}", bucket, path));
+        assert_eq!(
+            path(&item).as_deref(),
+            Some("kubernetes-jenkins/pr-logs/pull/42/pull-kubernetes-unit/7")
+        );
+        let gcs = item
+            .spec
+            .decoration_config
+            .as_mut()
+            .and_then(|config| config.gcs_configuration.as_mut())
+            .unwrap();
+        gcs.path_strategy = "explicit".to_string();
+        gcs.path_prefix = Some("prow/".to_string());
+        assert_eq!(
+            path(&item).as_deref(),
+            Some("kubernetes-jenkins/prow/pr-logs/pull/kubernetes_kubernetes/42/pull-kubernetes-unit/7")
+        );
+        item.spec.type_field = "periodic".to_string();
+        assert_eq!(
+            path(&item).as_deref(),
+            Some("kubernetes-jenkins/prow/logs/pull-kubernetes-unit/7")
+        );
+        assert_eq!(
+            view_path("https://prow.k8s.io/view/gs/kubernetes-jenkins/logs/ci-job/7"),
+            Some((
+                "kubernetes-jenkins".to_string(),
+                "logs/ci-job/7".to_string()
+            ))
+        );
+        assert_eq!(
+            view_path("https://prow.k8s.io/view/s3/bucket/logs/ci-job/7"),
+            None
+        );
     }
 }
-
-#[cfg(test)]
-mod tests {}
@@ -0,0 +1,38 @@
+use crate::analysis::rules::AnalysisConfig;
+use crate::collection::download::DownloadConfig;
+use crate::error::{Error, Result};
+use crate::identification::IdentificationConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Root of the Arcalog configuration file
+pub struct Config {
+    /// Configuration sections of the collectors, keyed by collector name
+    pub collection: Option<HashMap<String, serde_yaml::Value>>,
+    /// Path to the data directory
+    pub data: Option<String>,
+    /// Settings of the artifact downloads
+    #[serde(default)]
+    pub download: DownloadConfig,
+    #[serde(default)]
+    pub identification: IdentificationConfig,
+    #[serde(default)]
+    pub analysis: AnalysisConfig,
+}
+
+impl Config {
+    /// Reads the configuration from a YAML file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the configuration file
+    pub fn from_file(path: &str) -> Result<Self> {
+        let file = File::open(path).map_err(|e| Error::io(path, e))?;
+        serde_yaml::from_reader(file).map_err(|e| Error::parse(path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {}
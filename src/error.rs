@@ -0,0 +1,60 @@
+use std::fmt::Display;
+
+/// Errors returned by the collection, identification, and analysis steps
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// A remote source could not be reached or answered with an error status
+    #[error("request to {url} failed: {message}")]
+    Network { url: String, message: String },
+    /// Downloaded or stored data could not be parsed
+    #[error("failed to parse {what}: {message}")]
+    Parse { what: String, message: String },
+    /// A file or directory could not be read or written
+    #[error("failed to access {path}: {source}")]
+    Io {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+    /// The build index could not be opened, queried or updated
+    #[error("build index error: {0}")]
+    Database(#[from] rusqlite::Error),
+    /// The requested build, file or link does not exist
+    #[error("{0}")]
+    NotFound(String),
+    /// The request itself is invalid, e.g. because a required parameter is empty
+    #[error("{0}")]
+    InvalidInput(String),
+}
+
+/// Result type used throughout Arcalog
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Creates a network error for the given URL
+    pub fn network(url: &str, message: impl Display) -> Self {
+        Error::Network {
+            url: url.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    /// Creates a parse error describing what could not be parsed
+    pub fn parse(what: &str, message: impl Display) -> Self {
+        Error::Parse {
+            what: what.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    /// Creates an IO error for the given path
+    pub fn io(path: &str, source: std::io::Error) -> Self {
+        Error::Io {
+            path: path.to_string(),
+            source,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {}
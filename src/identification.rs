@@ -1,19 +1,43 @@
+pub mod archives;
+pub mod blocks;
+pub mod junit;
+pub mod templates;
+
+use crate::collection::manifest::is_internal_file;
 use crate::collection::prow::download_artifacts;
+use crate::collection::BuildRecord;
+use crate::error::{Error, Result};
 use crate::system::create_file_index;
+use archives::read_artifact;
+use blocks::{BlockKind, Continuation};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+use std::collections::VecDeque;
 use std::io::BufRead;
 use std::path::Path;
+use std::sync::OnceLock;
+use templates::TemplatesConfig;
 
 pub struct Nouns {
     pub nouns: Vec<String>,
 }
 
+impl Default for Nouns {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Nouns {
     pub fn new() -> Self {
         Nouns {
             nouns: vec![
                 "error".to_string(),
+                "exception".to_string(),
                 "failure".to_string(),
                 "mistake".to_string(),
+                "panic".to_string(),
                 "problem".to_string(),
                 "timeout".to_string(),
                 "warning".to_string(),
@@ -34,6 +58,12 @@ pub struct Verbs {
     pub verbs: Vec<String>,
 }
 
+impl Default for Verbs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Verbs {
     pub fn new() -> Self {
         Verbs {
@@ -101,10 +131,18 @@ pub struct Adjectives {
     pub adjectives: Vec<String>,
 }
 
+impl Default for Adjectives {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Adjectives {
     pub fn new() -> Self {
         Adjectives {
             adjectives: vec![
+                "failed".to_string(),
+                "fatal".to_string(),
                 "unreachable".to_string(),
                 "unresponsive".to_string(),
                 "unsigned".to_string(),
@@ -113,53 +151,796 @@ impl Adjectives {
             ],
         }
     }
+
+    pub fn add(&mut self, adjective: String) {
+        self.adjectives.push(adjective);
+    }
+
+    pub fn list(&self) -> Vec<String> {
+        self.adjectives.clone()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// The failure-relevant corpora a term can belong to
+pub enum Corpus {
+    Noun,
+    Verb,
+    Adjective,
+}
+
+impl Corpus {
+    /// Regular expression matching the inflections allowed after a term of this corpus
+    fn inflections(&self) -> &'static str {
+        match self {
+            Self::Noun => "(?:s|es)?",
+            Self::Verb => "(?:s|es|ed|d|ing)?",
+            Self::Adjective => "",
+        }
+    }
+}
+
+/// Matches log lines against all terms of the failure-relevant corpora at once
+///
+/// Terms are matched case-insensitively and on word boundaries, allowing plurals for nouns and
+/// common inflections for verbs. A line counts as an event if it contains a noun or an adjective;
+/// verbs such as `get` or `run` appear in most log lines and are only recorded alongside them.
+/// Lines are checked against the nouns and adjectives first, so that the slower extraction of
+/// the terms only runs for the few lines that are events.
+pub struct Matcher {
+    /// Nouns and adjectives without capture groups
+    relevant: Regex,
+    /// All terms with one capture group each, in the order of `terms`
+    regex: Regex,
+    terms: Vec<(String, Corpus)>,
+}
+
+/// Compiles a case-insensitive pattern matching any of the given terms on word boundaries
+///
+/// # Arguments
+///
+/// * `terms` - The terms together with their corpus
+/// * `capture` - If set to true, every term gets its own capture group
+fn terms_regex<'a>(terms: impl Iterator<Item = &'a (String, Corpus)>, capture: bool) -> Regex {
+    let alternatives = terms
+        .map(|(term, corpus)| {
+            let words = term
+                .split_whitespace()
+                .map(regex::escape)
+                .collect::<Vec<String>>()
+                .join(r"\s+");
+            match capture {
+                true => format!("({}{})", words, corpus.inflections()),
+                false => format!("(?:{}{})", words, corpus.inflections()),
+            }
+        })
+        .collect::<Vec<String>>();
+    let pattern = if alternatives.is_empty() {
+        // Never matches anything
+        r"[^\s\S]".to_string()
+    } else {
+        format!(r"(?i)\b(?:{})\b", alternatives.join("|"))
+    };
+    Regex::new(&pattern).expect("escaped corpus terms always form a valid pattern")
+}
+
+impl Default for Matcher {
+    fn default() -> Self {
+        Matcher::new(&Nouns::new(), &Verbs::new(), &Adjectives::new())
+    }
+}
+
+impl Matcher {
+    /// Compiles a matcher from the given corpora
+    ///
+    /// # Arguments
+    ///
+    /// * `nouns` - Nouns indicating a failure
+    /// * `verbs` - Verbs describing failing actions
+    /// * `adjectives` - Adjectives indicating a failure
+    pub fn new(nouns: &Nouns, verbs: &Verbs, adjectives: &Adjectives) -> Self {
+        let mut terms: Vec<(String, Corpus)> = Vec::new();
+        // Nouns and adjectives come first so that they win over verbs matching the same text
+        for (corpus, list) in [
+            (Corpus::Noun, &nouns.nouns),
+            (Corpus::Adjective, &adjectives.adjectives),
+            (Corpus::Verb, &verbs.verbs),
+        ] {
+            for term in list {
+                let term = term.trim().to_lowercase();
+                if !term.is_empty() && !terms.iter().any(|(known, _)| known == &term) {
+                    terms.push((term, corpus));
+                }
+            }
+        }
+        Matcher {
+            relevant: terms_regex(
+                terms.iter().filter(|(_, corpus)| *corpus != Corpus::Verb),
+                false,
+            ),
+            regex: terms_regex(terms.iter(), true),
+            terms,
+        }
+    }
+
+    /// Finds all corpus terms in a line
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - The log line to search
+    ///
+    /// # Returns
+    ///
+    /// The distinct corpus terms found in the line together with their corpus, in order of appearance
+    pub fn find_terms(&self, line: &str) -> Vec<(String, Corpus)> {
+        let mut found: Vec<(String, Corpus)> = Vec::new();
+        if !self.regex.is_match(line) {
+            return found;
+        }
+        for captures in self.regex.captures_iter(line) {
+            let Some(index) = (1..captures.len()).find(|index| captures.get(*index).is_some())
+            else {
+                continue;
+            };
+            let term = &self.terms[index - 1];
+            if !found.contains(term) {
+                found.push(term.clone());
+            }
+        }
+        found
+    }
+
+    /// Checks whether a line is an event
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - The log line to check
+    ///
+    /// # Returns
+    ///
+    /// The corpus terms found in the line if it contains at least one noun or adjective
+    pub fn match_event(&self, line: &str) -> Option<Vec<String>> {
+        if !self.relevant.is_match(line) {
+            return None;
+        }
+        let found = self.find_terms(line);
+        if found.iter().any(|(_, corpus)| *corpus != Corpus::Verb) {
+            Some(found.into_iter().map(|(term, _)| term).collect())
+        } else {
+            None
+        }
+    }
+}
+
+#[skip_serializing_none]
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Changes to a single corpus, applied in the order `replace`, `extend`, `exclude`
+pub struct CorpusConfig {
+    /// Replaces all terms of the corpus
+    pub replace: Option<Vec<String>>,
+    /// Adds terms to the corpus
+    #[serde(default)]
+    pub extend: Vec<String>,
+    /// Removes terms from the corpus
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+impl CorpusConfig {
+    fn apply(&self, terms: &mut Vec<String>) {
+        if let Some(replace) = &self.replace {
+            *terms = replace.clone();
+        }
+        for term in &self.extend {
+            if !terms.contains(term) {
+                terms.push(term.clone());
+            }
+        }
+        terms.retain(|term| {
+            !self
+                .exclude
+                .iter()
+                .any(|excluded| excluded.eq_ignore_ascii_case(term))
+        });
+    }
+}
+
+#[skip_serializing_none]
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Changes to the failure-relevant corpora
+pub struct CorporaConfig {
+    pub nouns: Option<CorpusConfig>,
+    pub verbs: Option<CorpusConfig>,
+    pub adjectives: Option<CorpusConfig>,
+}
+
+impl CorporaConfig {
+    fn apply(&self, nouns: &mut Nouns, verbs: &mut Verbs, adjectives: &mut Adjectives) {
+        if let Some(config) = &self.nouns {
+            config.apply(&mut nouns.nouns);
+        }
+        if let Some(config) = &self.verbs {
+            config.apply(&mut verbs.verbs);
+        }
+        if let Some(config) = &self.adjectives {
+            config.apply(&mut adjectives.adjectives);
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Corpora changes that only apply to jobs whose name matches a regular expression
+pub struct JobCorporaConfig {
+    /// Regular expression that has to match the whole job name
+    pub job: String,
+    #[serde(flatten)]
+    pub corpora: CorporaConfig,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// `identification` section of the configuration file
+pub struct IdentificationConfig {
+    /// Changes applied to the corpora for all jobs
+    #[serde(flatten)]
+    pub corpora: CorporaConfig,
+    /// Changes applied on top for individual jobs, in the given order
+    #[serde(default)]
+    pub jobs: Vec<JobCorporaConfig>,
+    /// Number of lines before and after each event that are included as its context
+    #[serde(default)]
+    pub context: ContextConfig,
+    /// Limits of events spanning several lines, like Go panics
+    #[serde(default)]
+    pub blocks: BlocksConfig,
+    /// Settings for grouping near-identical events into templates
+    #[serde(default)]
+    pub templates: TemplatesConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Number of surrounding lines stored with every event
+pub struct ContextConfig {
+    #[serde(default = "ContextConfig::default_lines")]
+    pub before: usize,
+    #[serde(default = "ContextConfig::default_lines")]
+    pub after: usize,
+}
+
+impl ContextConfig {
+    fn default_lines() -> usize {
+        2
+    }
+}
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        ContextConfig {
+            before: Self::default_lines(),
+            after: Self::default_lines(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Limits of multi-line events
+pub struct BlocksConfig {
+    /// Maximum number of lines of a single event, after which the following lines are scanned
+    /// on their own, e.g. those of a long goroutine dump
+    #[serde(default = "BlocksConfig::default_max_lines")]
+    pub max_lines: usize,
+}
+
+impl BlocksConfig {
+    fn default_max_lines() -> usize {
+        200
+    }
+}
+
+impl Default for BlocksConfig {
+    fn default() -> Self {
+        BlocksConfig {
+            max_lines: Self::default_max_lines(),
+        }
+    }
 }
 
-#[derive(Debug)]
-/// Individual log events including their build ID, metadata, and content
+impl IdentificationConfig {
+    /// Creates the matcher for the given job from the default corpora and the configured changes
+    ///
+    /// # Arguments
+    ///
+    /// * `job` - Name of the job whose events are identified
+    ///
+    /// # Returns
+    ///
+    /// The compiled `Matcher`, or a parse error if a job name pattern is invalid
+    pub fn matcher(&self, job: &str) -> Result<Matcher> {
+        let mut nouns = Nouns::new();
+        let mut verbs = Verbs::new();
+        let mut adjectives = Adjectives::new();
+        self.corpora.apply(&mut nouns, &mut verbs, &mut adjectives);
+        for job_config in &self.jobs {
+            let pattern = Regex::new(&format!("^(?:{})$", job_config.job))
+                .map_err(|e| Error::parse("identification job pattern", e))?;
+            if pattern.is_match(job) {
+                job_config
+                    .corpora
+                    .apply(&mut nouns, &mut verbs, &mut adjectives);
+            }
+        }
+        Ok(Matcher::new(&nouns, &verbs, &adjectives))
+    }
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+/// Individual log events including their build ID, location, and content
 pub struct Event {
     pub build_id: String,
-    pub metadata: Option<Vec<String>>,
+    /// Path of the artifact containing the event, relative to the artifact folder of the build
+    pub path: String,
+    /// Line number of the event within the artifact, starting at 1
+    pub line: usize,
+    /// Line number of the last line of the event, which differs from `line` for multi-line events
+    pub end_line: usize,
+    /// Kind of multi-line block, such as a stack trace, the event consists of
+    pub kind: Option<BlockKind>,
+    /// Byte offset of the start of the event within the artifact
+    pub offset: u64,
     pub content: String,
+    /// Corpus terms found in the event
+    pub terms: Vec<String>,
+    /// Lines preceding the event
+    pub before: Vec<String>,
+    /// Lines following the event
+    pub after: Vec<String>,
 }
 
 /// Collects all log events for a given build ID
 ///
+/// Compressed artifacts are decompressed and archives are searched as well, with events of files
+/// within archives having a path like `must-gather.tar.gz!/namespaces/foo/pod.log`.
+///
 /// # Arguments
 ///
-/// * `build_id` - The build ID for the requested job
+/// * `build` - The requested build
 /// * `path` - The root data directory
+/// * `config` - Identification settings from the configuration file
 ///
 /// # Returns
 ///
 /// A vector of log events containing elements from the failure-relevant corpora
-pub async fn collect_events(build_id: String, path: String) -> Vec<String> {
-    let artifact_path = format!("{}/prow/artifacts/{}", path, build_id);
+pub async fn collect_events(
+    build: &BuildRecord,
+    path: &str,
+    config: &IdentificationConfig,
+) -> Result<Vec<Event>> {
+    let artifact_path = build.artifact_path(path);
     if !Path::new(&artifact_path).exists() {
-        download_artifacts(&build_id, &path).await;
+        download_artifacts(build, path).await?;
     }
-    let file_index = create_file_index(artifact_path.clone()).await;
-    let mut events: Vec<String> = Vec::new();
+    let mut file_index = create_file_index(artifact_path.clone()).await;
+    // JUnit reports are parsed into test results instead
+    file_index.retain(|file| !junit::is_junit_file(file) && !is_internal_file(file));
+    file_index.sort();
+    let matcher = config.matcher(&build.job)?;
+    let mut events: Vec<Event> = Vec::new();
 
     for file in file_index {
-        let file_open = std::fs::File::open(&file);
-        if file_open.is_ok() {
-            let file_contents = file_open.unwrap();
-            let reader = std::io::BufReader::new(file_contents);
-            for line in reader.lines() {
-                if line.is_ok() {
-                    let line_contents = line.unwrap();
-                    if line_contents.contains(Nouns::new().nouns[0].as_str()) {
-                        let event = line_contents;
+        let relative_path = Path::new(&file)
+            .strip_prefix(&artifact_path)
+            .map(|relative| relative.to_string_lossy().to_string())
+            .unwrap_or(file.clone());
+        let result = read_artifact(&file, &relative_path, &mut |path, reader| {
+            // JUnit reports within archives are skipped as well
+            if junit::is_junit_file(path) {
+                return;
+            }
+            let mut file_events = scan_events(reader, &matcher, &config.context, &config.blocks);
+            for event in &mut file_events {
+                event.build_id = build.build_id.clone();
+                event.path = path.to_string();
+            }
+            events.append(&mut file_events);
+        });
+        if let Err(e) = result {
+            println!(
+                "⛔\t\x1b[93m\x1b[1mSkipping the rest of {}: {}\x1b[0m",
+                relative_path, e
+            );
+        }
+    }
+    Ok(events)
+}
+
+/// Multi-line block that is being read by `scan_events`
+struct Block {
+    kind: BlockKind,
+    line: usize,
+    offset: u64,
+    lines: Vec<String>,
+    before: Vec<String>,
+}
+
+impl Block {
+    /// Turns the block into a single event
+    ///
+    /// A block consisting of its first line only, like an exception name without any stack
+    /// frames, is treated like any other line and is only an event if the matcher accepts it.
+    fn into_event(mut self, matcher: &Matcher) -> Option<Event> {
+        while self.lines.len() > 1 && self.lines.last().is_some_and(|l| l.trim().is_empty()) {
+            self.lines.pop();
+        }
+        let (kind, terms) = if self.lines.len() == 1 {
+            (None, matcher.match_event(&self.lines[0])?)
+        } else {
+            let terms = matcher
+                .find_terms(&self.lines.join("\n"))
+                .into_iter()
+                .map(|(term, _)| term)
+                .collect();
+            (Some(self.kind), terms)
+        };
+        Some(Event {
+            line: self.line,
+            end_line: self.line + self.lines.len() - 1,
+            kind,
+            offset: self.offset,
+            content: self.lines.join("\n"),
+            terms,
+            before: self.before,
+            ..Default::default()
+        })
+    }
+}
+
+/// Finds all events in a log including their position and surrounding lines
+///
+/// Go panics, Java exceptions and Python tracebacks are reported as a single event spanning all
+/// of their lines, up to a maximum number of lines. Lines that are not valid UTF-8 are converted
+/// lossily instead of ending the scan.
+///
+/// # Arguments
+///
+/// * `reader` - The log to scan
+/// * `matcher` - The compiled failure-relevant corpora
+/// * `context` - Number of lines to keep before and after each event
+/// * `blocks` - Limits of events spanning several lines
+///
+/// # Returns
+///
+/// The events of the log without build ID and path
+pub fn scan_events<R: BufRead>(
+    mut reader: R,
+    matcher: &Matcher,
+    context: &ContextConfig,
+    blocks: &BlocksConfig,
+) -> Vec<Event> {
+    let mut events: Vec<Event> = Vec::new();
+    let mut before: VecDeque<String> = VecDeque::with_capacity(context.before + 1);
+    // Events still waiting for lines after them, as indices into `events`
+    let mut pending: Vec<usize> = Vec::new();
+    // Multi-line block that is still being read
+    let mut current: Option<Block> = None;
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut offset: u64 = 0;
+    let mut line_number: usize = 0;
+
+    loop {
+        buffer.clear();
+        let read = match reader.read_until(b'\n', &mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(read) => read,
+        };
+        line_number += 1;
+        let line = String::from_utf8_lossy(&buffer)
+            .trim_end_matches(['\n', '\r'])
+            .to_string();
+
+        // Lines belonging to a multi-line block are not checked individually
+        let mut consumed = false;
+        let mut closed: Option<Block> = None;
+        if let Some(mut block) = current.take() {
+            let continuation = match block.lines.len() >= blocks.max_lines {
+                true => Continuation::End,
+                false => block.kind.continuation(block.lines.last(), &line),
+            };
+            match continuation {
+                Continuation::Continue => {
+                    block.lines.push(line.clone());
+                    current = Some(block);
+                    consumed = true;
+                }
+                Continuation::Close => {
+                    block.lines.push(line.clone());
+                    closed = Some(block);
+                    consumed = true;
+                }
+                Continuation::End => {
+                    if let Some(event) = block.into_event(matcher) {
                         events.push(event);
+                        if context.after > 0 {
+                            pending.push(events.len() - 1);
+                        }
                     }
                 }
             }
-            //}
         }
+
+        pending.retain(|index| {
+            let event = &mut events[*index];
+            event.after.push(line.clone());
+            event.after.len() < context.after
+        });
+
+        let event = match closed {
+            Some(block) => block.into_event(matcher),
+            None if consumed => None,
+            None => match BlockKind::detect(&line) {
+                Some(kind) => {
+                    current = Some(Block {
+                        kind,
+                        line: line_number,
+                        offset,
+                        lines: vec![line.clone()],
+                        before: before.iter().cloned().collect(),
+                    });
+                    None
+                }
+                None => matcher.match_event(&line).map(|terms| Event {
+                    line: line_number,
+                    end_line: line_number,
+                    offset,
+                    content: line.clone(),
+                    terms,
+                    before: before.iter().cloned().collect(),
+                    ..Default::default()
+                }),
+            },
+        };
+        if let Some(event) = event {
+            events.push(event);
+            if context.after > 0 {
+                pending.push(events.len() - 1);
+            }
+        }
+
+        if context.before > 0 {
+            if before.len() == context.before {
+                before.pop_front();
+            }
+            before.push_back(line);
+        }
+        offset += read as u64;
+    }
+    if let Some(event) = current.and_then(|block| block.into_event(matcher)) {
+        events.push(event);
+    }
+    events
+}
+
+/// Normalises a log event so that the same event from different builds compares as equal
+///
+/// Timestamps, UUIDs, IP addresses, long hexadecimal strings and numbers are replaced with
+/// placeholders and consecutive whitespace is collapsed.
+///
+/// # Arguments
+///
+/// * `event` - The raw log event
+///
+/// # Returns
+///
+/// The normalised log event
+pub fn normalize_event(event: &str) -> String {
+    static MASKS: OnceLock<Vec<(Regex, &str)>> = OnceLock::new();
+    let masks = MASKS.get_or_init(|| {
+        vec![
+            (
+                Regex::new(r"\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2}(\.\d+)?(Z|[+-]\d{2}:?\d{2})?")
+                    .unwrap(),
+                "<TIME>",
+            ),
+            (
+                Regex::new(r"\b\d{2}:\d{2}:\d{2}(\.\d+)?\b").unwrap(),
+                "<TIME>",
+            ),
+            (
+                Regex::new(r"(?i)\b[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}\b")
+                    .unwrap(),
+                "<UUID>",
+            ),
+            (
+                Regex::new(r"\b\d{1,3}\.\d{1,3}\.\d{1,3}\.\d{1,3}(:\d+)?\b").unwrap(),
+                "<IP>",
+            ),
+            (
+                Regex::new(r"(?i)\b(0x[0-9a-f]+|[0-9a-f]{8,})\b").unwrap(),
+                "<HEX>",
+            ),
+            (Regex::new(r"\d+").unwrap(), "<NUM>"),
+            (Regex::new(r"\s+").unwrap(), " "),
+        ]
+    });
+    let mut normalized = event.trim().to_string();
+    for (regex, replacement) in masks {
+        normalized = regex.replace_all(&normalized, *replacement).to_string();
     }
-    return events;
+    normalized
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Checks that terms of all corpora are matched case-insensitively, on word boundaries and with inflections
+    fn test_matcher() {
+        let matcher = Matcher::default();
+        assert_eq!(
+            matcher.match_event("--- FAILED: TestConnect (12.3s)"),
+            Some(vec!["failed".to_string()])
+        );
+        assert_eq!(
+            matcher.match_event("Timeout while waiting for pods"),
+            Some(vec!["timeout".to_string()])
+        );
+        assert_eq!(
+            matcher.match_event("panic: runtime error: index out of range"),
+            Some(vec!["panic".to_string(), "error".to_string()])
+        );
+        assert_eq!(
+            matcher.match_event("Failed to connect: host unreachable"),
+            Some(vec![
+                "failed".to_string(),
+                "connect".to_string(),
+                "unreachable".to_string()
+            ])
+        );
+        assert_eq!(matcher.match_event("Running 12 tests"), None);
+        assert_eq!(matcher.match_event("terrorist errorless"), None);
+    }
+
+    #[test]
+    /// Checks that corpora are replaced, extended and excluded globally and per job
+    fn test_identification_config() {
+        let config: IdentificationConfig = serde_yaml::from_str(
+            "
+nouns:
+  extend: [oops]
+  exclude: [Warning]
+jobs:
+  - job: \"ci-kernel-.*\"
+    adjectives:
+      replace: [tainted]
+",
+        )
+        .unwrap();
+        let matcher = config.matcher("pull-e2e").unwrap();
+        assert!(matcher.match_event("oops, something went wrong").is_some());
+        assert!(matcher.match_event("warning: deprecated flag").is_none());
+        assert!(matcher.match_event("node unreachable").is_some());
+
+        let matcher = config.matcher("ci-kernel-build").unwrap();
+        assert!(matcher.match_event("kernel tainted").is_some());
+        assert!(matcher.match_event("node unreachable").is_none());
+    }
+
+    #[test]
+    /// Checks that events carry their line number, byte offset and surrounding lines
+    fn test_scan_events() {
+        let log = "starting\r\nstep 1\nERROR: step 2 failed\nstep 3\nwarning: slow\n";
+        let context = ContextConfig {
+            before: 1,
+            after: 2,
+        };
+        let events = scan_events(
+            log.as_bytes(),
+            &Matcher::default(),
+            &context,
+            &BlocksConfig::default(),
+        );
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].line, 3);
+        assert_eq!(events[0].offset, 17);
+        assert_eq!(events[0].content, "ERROR: step 2 failed");
+        assert_eq!(events[0].terms, vec!["error", "failed"]);
+        assert_eq!(events[0].before, vec!["step 1"]);
+        assert_eq!(events[0].after, vec!["step 3", "warning: slow"]);
+        assert_eq!(events[1].line, 5);
+        assert_eq!(events[1].before, vec!["step 3"]);
+        assert!(events[1].after.is_empty());
+    }
+
+    #[test]
+    /// Checks that stack traces and panics are reported as one event spanning all of their lines
+    fn test_scan_blocks() {
+        let log = "\
+=== RUN TestConnect
+panic: runtime error: invalid memory address or nil pointer dereference
+
+goroutine 1 [running]:
+main.main()
+\t/src/main.go:8 +0x1d
+exit status 2
+Exception in thread \"main\" java.lang.IllegalStateException: closed
+\tat com.example.Pool.get(Pool.java:12)
+Caused by: java.io.IOException: broken pipe
+\t... 3 more
+done
+Traceback (most recent call last):
+  File \"test.py\", line 3, in <module>
+    connect()
+ConnectionError: refused
+java.lang.NullPointerException
+";
+        let context = ContextConfig {
+            before: 1,
+            after: 1,
+        };
+        let events = scan_events(
+            log.as_bytes(),
+            &Matcher::default(),
+            &context,
+            &BlocksConfig::default(),
+        );
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].kind, Some(BlockKind::GoPanic));
+        assert_eq!((events[0].line, events[0].end_line), (2, 7));
+        assert_eq!(events[0].before, vec!["=== RUN TestConnect"]);
+        assert!(events[0].content.ends_with("exit status 2"));
+        assert_eq!(events[1].kind, Some(BlockKind::JavaException));
+        assert_eq!((events[1].line, events[1].end_line), (8, 11));
+        assert_eq!(events[1].after, vec!["done"]);
+        assert_eq!(events[2].kind, Some(BlockKind::PythonTraceback));
+        assert_eq!((events[2].line, events[2].end_line), (13, 16));
+        assert_eq!(events[2].terms, vec!["test", "connect"]);
+    }
+
+    #[test]
+    /// Checks that Go panics end on a second blank line and long blocks are cut at the maximum
+    fn test_scan_blocks_limits() {
+        let log = "\
+panic: boom
+
+goroutine 1 [running]:
+main.main()
+
+
+  indented: yaml
+panic: again
+  a: 1
+  b: 2
+  c: 3
+  d: 4
+";
+        let context = ContextConfig {
+            before: 0,
+            after: 0,
+        };
+        let events = scan_events(
+            log.as_bytes(),
+            &Matcher::default(),
+            &context,
+            &BlocksConfig { max_lines: 5 },
+        );
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].kind, Some(BlockKind::GoPanic));
+        assert_eq!((events[0].line, events[0].end_line), (1, 4));
+        assert_eq!(events[1].kind, Some(BlockKind::GoPanic));
+        assert_eq!((events[1].line, events[1].end_line), (8, 12));
+    }
+
+    #[test]
+    /// Checks that variable tokens are masked so that equivalent events from different builds match
+    fn test_normalize_event() {
+        let first = normalize_event(
+            "2023-11-02T10:15:00Z error: pod 3f2b1c9e-1a2b-4c3d-8e9f-0a1b2c3d4e5f on 10.0.0.12:6443 exited with 137",
+        );
+        let second = normalize_event(
+            "2023-11-03T08:01:59Z error: pod 9a8b7c6d-5e4f-4a3b-2c1d-0e9f8a7b6c5d on 10.0.3.7:6443 exited with 1",
+        );
+        assert_eq!(first, second);
+        assert_eq!(first, "<TIME> error: pod <UUID> on <IP> exited with <NUM>");
+    }
+}
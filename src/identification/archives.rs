@@ -0,0 +1,315 @@
+use crate::error::{Error, Result};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Cursor, Read};
+
+/// Separator between the path of an archive and the path of a file within it,
+/// e.g. `must-gather.tar.gz!/namespaces/foo/pod.log`
+pub const ARCHIVE_SEPARATOR: &str = "!/";
+
+/// Maximum number of nested archives and compressed files that are opened
+const MAX_DEPTH: usize = 8;
+
+/// Zip archives need random access, so those within other archives are read into memory up to this size
+const MAX_NESTED_ZIP_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Decompressed gzip, xz and zstd streams are read up to this size, to guard against decompression bombs
+const MAX_DECOMPRESSED_SIZE: u64 = 2 * 1024 * 1024 * 1024;
+
+/// Format of a file as detected from its first bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Gzip,
+    Xz,
+    Zstd,
+    Zip,
+    Tar,
+    Plain,
+}
+
+impl Format {
+    /// Detects compressed files and archives by their magic numbers
+    ///
+    /// # Arguments
+    ///
+    /// * `header` - Up to the first 512 bytes of the file
+    pub fn detect(header: &[u8]) -> Self {
+        if header.starts_with(&[0x1f, 0x8b]) {
+            Format::Gzip
+        } else if header.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Format::Xz
+        } else if header.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Format::Zstd
+        } else if header.starts_with(b"PK\x03\x04") || header.starts_with(b"PK\x05\x06") {
+            Format::Zip
+        } else if header.get(257..262) == Some(b"ustar") {
+            Format::Tar
+        } else {
+            Format::Plain
+        }
+    }
+}
+
+/// Reads an artifact, decompressing it and walking through archives
+///
+/// Gzip, xz and zstd files are decompressed while they are read, and every file within tar and
+/// zip archives is read as well, including nested archives and compressed files. Files within
+/// tar archives that cannot be read are skipped.
+///
+/// # Arguments
+///
+/// * `file` - Path of the artifact file
+/// * `path` - Path of the artifact as it is reported, usually relative to the artifact folder of the build
+/// * `visit` - Called with the path and the content of every plain file, where files within
+///   archives have a path like `must-gather.tar.gz!/namespaces/foo/pod.log`
+///
+/// # Returns
+///
+/// An error if the artifact cannot be read, after `visit` has been called for all files before the error
+pub fn read_artifact(
+    file: &str,
+    path: &str,
+    visit: &mut dyn FnMut(&str, &mut dyn BufRead),
+) -> Result<()> {
+    let mut reader = File::open(file).map_err(|e| Error::io(file, e))?;
+    let mut header = Vec::with_capacity(4);
+    (&mut reader)
+        .take(4)
+        .read_to_end(&mut header)
+        .map_err(|e| Error::io(file, e))?;
+    if Format::detect(&header) == Format::Zip {
+        // The central directory at the end of a zip file is read directly from the file
+        return read_zip(
+            File::open(file).map_err(|e| Error::io(file, e))?,
+            path,
+            0,
+            visit,
+        );
+    }
+    let reader = Cursor::new(header).chain(reader);
+    read_content(Box::new(reader), path, 0, visit)
+}
+
+/// Reader failing once more than a maximum number of bytes has been read
+struct LimitedReader<R> {
+    reader: R,
+    remaining: u64,
+}
+
+impl<R> LimitedReader<R> {
+    fn new(reader: R) -> Self {
+        LimitedReader {
+            reader,
+            remaining: MAX_DECOMPRESSED_SIZE,
+        }
+    }
+}
+
+impl<R: Read> Read for LimitedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.reader.read(buf)?;
+        match self.remaining.checked_sub(read as u64) {
+            Some(remaining) => {
+                self.remaining = remaining;
+                Ok(read)
+            }
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "decompressed content exceeds {} bytes",
+                    MAX_DECOMPRESSED_SIZE
+                ),
+            )),
+        }
+    }
+}
+
+fn read_content<'a>(
+    mut reader: Box<dyn Read + 'a>,
+    path: &str,
+    depth: usize,
+    visit: &mut dyn FnMut(&str, &mut dyn BufRead),
+) -> Result<()> {
+    let mut header = Vec::with_capacity(512);
+    (&mut reader)
+        .take(512)
+        .read_to_end(&mut header)
+        .map_err(|e| Error::parse(path, e))?;
+    let format = match depth < MAX_DEPTH {
+        true => Format::detect(&header),
+        false => Format::Plain,
+    };
+    let reader = Cursor::new(header).chain(reader);
+    match format {
+        Format::Gzip => read_content(
+            Box::new(LimitedReader::new(flate2::read::MultiGzDecoder::new(
+                reader,
+            ))),
+            path,
+            depth + 1,
+            visit,
+        ),
+        Format::Xz => read_content(
+            Box::new(LimitedReader::new(xz2::read::XzDecoder::new_multi_decoder(
+                reader,
+            ))),
+            path,
+            depth + 1,
+            visit,
+        ),
+        Format::Zstd => {
+            let decoder = ruzstd::decoding::StreamingDecoder::new(reader)
+                .map_err(|e| Error::parse(path, e))?;
+            read_content(
+                Box::new(LimitedReader::new(decoder)),
+                path,
+                depth + 1,
+                visit,
+            )
+        }
+        Format::Tar => {
+            // A broken entry is skipped, so that the remaining files of the archive are still read
+            let mut archive = tar::Archive::new(reader);
+            for entry in archive.entries().map_err(|e| Error::parse(path, e))? {
+                let result = entry.map_err(|e| Error::parse(path, e)).and_then(|entry| {
+                    if !entry.header().entry_type().is_file() {
+                        return Ok(());
+                    }
+                    let entry_path = format!(
+                        "{}{}{}",
+                        path,
+                        ARCHIVE_SEPARATOR,
+                        entry.path().map_err(|e| Error::parse(path, e))?.display()
+                    );
+                    read_content(Box::new(entry), &entry_path, depth + 1, visit)
+                });
+                if let Err(e) = result {
+                    println!(
+                        "⛔\t\x1b[93m\x1b[1mSkipping a file of {}: {}\x1b[0m",
+                        path, e
+                    );
+                }
+            }
+            Ok(())
+        }
+        Format::Zip => {
+            let mut content = Vec::new();
+            reader
+                .take(MAX_NESTED_ZIP_SIZE + 1)
+                .read_to_end(&mut content)
+                .map_err(|e| Error::parse(path, e))?;
+            if content.len() as u64 > MAX_NESTED_ZIP_SIZE {
+                return Err(Error::parse(
+                    path,
+                    format!("nested zip archive exceeds {} bytes", MAX_NESTED_ZIP_SIZE),
+                ));
+            }
+            read_zip(Cursor::new(content), path, depth, visit)
+        }
+        Format::Plain => {
+            visit(path, &mut BufReader::new(reader));
+            Ok(())
+        }
+    }
+}
+
+fn read_zip<R: Read + std::io::Seek>(
+    reader: R,
+    path: &str,
+    depth: usize,
+    visit: &mut dyn FnMut(&str, &mut dyn BufRead),
+) -> Result<()> {
+    let mut archive = zip::ZipArchive::new(reader).map_err(|e| Error::parse(path, e))?;
+    for index in 0..archive.len() {
+        let file = archive.by_index(index).map_err(|e| Error::parse(path, e))?;
+        if file.is_dir() {
+            continue;
+        }
+        let file_path = format!("{}{}{}", path, ARCHIVE_SEPARATOR, file.name());
+        read_content(Box::new(file), &file_path, depth + 1, visit)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    /// Checks that files within compressed tar archives are read with their archive-internal path
+    fn test_read_artifact() {
+        let mut log = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        log.write_all(b"error: pod crashed\n").unwrap();
+        let log = log.finish().unwrap();
+        let mut archive = tar::Builder::new(Vec::new());
+        for (name, content) in [
+            ("namespaces/foo/pod.log", b"line 1\nline 2\n".as_slice()),
+            ("namespaces/foo/previous.log.gz", log.as_slice()),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            archive.append_data(&mut header, name, content).unwrap();
+        }
+        let mut file = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        file.write_all(&archive.into_inner().unwrap()).unwrap();
+        let path = std::env::temp_dir().join(format!("arcalog-archive-{}", std::process::id()));
+        std::fs::write(&path, file.finish().unwrap()).unwrap();
+
+        let mut files = Vec::new();
+        read_artifact(
+            &path.to_string_lossy(),
+            "must-gather.tar.gz",
+            &mut |path, reader| {
+                let mut content = String::new();
+                reader.read_to_string(&mut content).unwrap();
+                files.push((path.to_string(), content));
+            },
+        )
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            files,
+            vec![
+                (
+                    "must-gather.tar.gz!/namespaces/foo/pod.log".to_string(),
+                    "line 1\nline 2\n".to_string()
+                ),
+                (
+                    "must-gather.tar.gz!/namespaces/foo/previous.log.gz".to_string(),
+                    "error: pod crashed\n".to_string()
+                ),
+            ]
+        );
+        assert_eq!(Format::detect(b"plain text"), Format::Plain);
+    }
+
+    #[test]
+    /// Checks that a corrupt file within a tar archive is skipped and the following files are read
+    fn test_read_artifact_corrupt_entry() {
+        let mut archive = tar::Builder::new(Vec::new());
+        for (name, content) in [
+            ("broken.log.gz", b"\x1f\x8b\x08\x00 not gzip".as_slice()),
+            ("pod.log", b"error: pod crashed\n".as_slice()),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            archive.append_data(&mut header, name, content).unwrap();
+        }
+        let path = std::env::temp_dir().join(format!("arcalog-corrupt-{}", std::process::id()));
+        std::fs::write(&path, archive.into_inner().unwrap()).unwrap();
+
+        let mut files = Vec::new();
+        read_artifact(
+            &path.to_string_lossy(),
+            "must-gather.tar",
+            &mut |path, _| files.push(path.to_string()),
+        )
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(files, vec!["must-gather.tar!/pod.log"]);
+    }
+}
@@ -0,0 +1,203 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+/// Multi-line constructs that are reported as a single event
+pub enum BlockKind {
+    /// A Go `panic:` or `fatal error:` followed by goroutine dumps
+    GoPanic,
+    /// A Java exception followed by `at ...` frames and `Caused by:` sections
+    JavaException,
+    /// A Python `Traceback (most recent call last):` up to and including the exception line
+    PythonTraceback,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// How a line relates to the block that is currently being read
+pub enum Continuation {
+    /// The line belongs to the block and more lines may follow
+    Continue,
+    /// The line belongs to the block and is its last line
+    Close,
+    /// The line does not belong to the block anymore
+    End,
+}
+
+struct Patterns {
+    log_prefix: Regex,
+    go_frame: Regex,
+    java_start: Regex,
+    java_frame: Regex,
+}
+
+fn patterns() -> &'static Patterns {
+    static PATTERNS: OnceLock<Patterns> = OnceLock::new();
+    PATTERNS.get_or_init(|| Patterns {
+        log_prefix: Regex::new(
+            r"^(?:(?:\d{4}[-/]\d{2}[-/]\d{2}[T ]\d{2}:\d{2}:\d{2}(?:[.,]\d+)?(?:Z|[+-]\d{2}:?\d{2})?|[IWEF]\d{4} \d{2}:\d{2}:\d{2}\.\d+\s+\d+ [^\s\]]+\]) )+",
+        )
+        .unwrap(),
+        go_frame: Regex::new(r"^([\w./*\[\]{}-]+\(.*\)|\S+\.go:\d+.*)$").unwrap(),
+        java_start: Regex::new(
+            r#"^\s*(Exception in thread "[^"]*" )?([a-zA-Z_$][\w$]*\.)+[A-Z][\w$]*(Exception|Error|Throwable)(:.*)?$"#,
+        )
+        .unwrap(),
+        java_frame: Regex::new(
+            r"^(\s+at \S|\s*Caused by: |\s*Suppressed: |\s+\.\.\. \d+ (more|common frames omitted))",
+        )
+        .unwrap(),
+    })
+}
+
+/// Removes leading timestamps and klog headers, e.g. `2024/01/01 12:00:00 ` or
+/// `E0101 12:00:00.000000   12345 main.go:42] `, which prefix the lines of many build logs
+fn strip_log_prefix(line: &str) -> &str {
+    match patterns().log_prefix.find(line) {
+        Some(prefix) => &line[prefix.end()..],
+        None => line,
+    }
+}
+
+impl BlockKind {
+    /// Checks whether a line starts a multi-line block, ignoring a leading timestamp or klog header
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - The log line to check
+    ///
+    /// # Returns
+    ///
+    /// The kind of block started by the line, if any
+    pub fn detect(line: &str) -> Option<BlockKind> {
+        let line = strip_log_prefix(line);
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("panic: ") || trimmed.starts_with("fatal error: ") {
+            Some(BlockKind::GoPanic)
+        } else if trimmed.starts_with("Traceback (most recent call last):") {
+            Some(BlockKind::PythonTraceback)
+        } else if patterns().java_start.is_match(line) {
+            Some(BlockKind::JavaException)
+        } else {
+            None
+        }
+    }
+
+    /// Checks whether a line continues a block of this kind, ignoring a leading timestamp or
+    /// klog header
+    ///
+    /// # Arguments
+    ///
+    /// * `previous` - The last line already read into the block
+    /// * `line` - The log line following the lines already read into the block
+    ///
+    /// # Returns
+    ///
+    /// Whether the line belongs to the block and whether it ends the block
+    pub fn continuation(&self, previous: Option<&String>, line: &str) -> Continuation {
+        let line = strip_log_prefix(line);
+        let indented = line.starts_with([' ', '\t']);
+        let blank = line.trim().is_empty();
+        match self {
+            // Goroutines are separated by single blank lines, so a second one ends the panic
+            Self::GoPanic
+                if blank
+                    && previous
+                        .is_some_and(|previous| strip_log_prefix(previous).trim().is_empty()) =>
+            {
+                Continuation::End
+            }
+            Self::GoPanic => {
+                if blank
+                    || indented
+                    || line.starts_with("goroutine ")
+                    || line.starts_with("created by ")
+                    || line.starts_with("[signal ")
+                    || patterns().go_frame.is_match(line)
+                {
+                    Continuation::Continue
+                } else if line.starts_with("exit status ") {
+                    Continuation::Close
+                } else {
+                    Continuation::End
+                }
+            }
+            Self::JavaException => {
+                if patterns().java_frame.is_match(line) {
+                    Continuation::Continue
+                } else {
+                    Continuation::End
+                }
+            }
+            Self::PythonTraceback => {
+                if indented {
+                    Continuation::Continue
+                } else if blank {
+                    Continuation::End
+                } else {
+                    Continuation::Close
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Checks that the start lines of all block kinds are recognised
+    fn test_detect() {
+        assert_eq!(
+            BlockKind::detect("panic: runtime error: invalid memory address"),
+            Some(BlockKind::GoPanic)
+        );
+        assert_eq!(
+            BlockKind::detect("Traceback (most recent call last):"),
+            Some(BlockKind::PythonTraceback)
+        );
+        assert_eq!(
+            BlockKind::detect(
+                "Exception in thread \"main\" java.lang.IllegalStateException: closed"
+            ),
+            Some(BlockKind::JavaException)
+        );
+        assert_eq!(BlockKind::detect("error: not a block"), None);
+    }
+
+    #[test]
+    /// Checks that blocks are recognised behind timestamps and klog headers
+    fn test_detect_prefixed() {
+        assert_eq!(
+            BlockKind::detect("2024/01/01 12:00:00 panic: runtime error: index out of range"),
+            Some(BlockKind::GoPanic)
+        );
+        assert_eq!(
+            BlockKind::detect("E0101 12:00:00.000000   12345 main.go:42] panic: boom"),
+            Some(BlockKind::GoPanic)
+        );
+        assert_eq!(
+            BlockKind::detect("2024-01-01T12:00:00.123Z Traceback (most recent call last):"),
+            Some(BlockKind::PythonTraceback)
+        );
+        let previous = "2024/01/01 12:00:00 panic: boom".to_string();
+        assert_eq!(
+            BlockKind::GoPanic.continuation(
+                Some(&previous),
+                "2024/01/01 12:00:00 goroutine 1 [running]:"
+            ),
+            Continuation::Continue
+        );
+        assert_eq!(
+            BlockKind::GoPanic
+                .continuation(Some(&previous), "2024/01/01 12:00:00 \t/src/main.go:8"),
+            Continuation::Continue
+        );
+        assert_eq!(
+            BlockKind::GoPanic.continuation(Some(&previous), "2024/01/01 12:00:01 level=info done"),
+            Continuation::End
+        );
+    }
+}
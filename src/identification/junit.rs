@@ -0,0 +1,338 @@
+use crate::collection::manifest::{sha256, Manifest};
+use crate::collection::BuildRecord;
+use crate::error::{Error, Result};
+use crate::system::{check_slash, create_dir, create_file_index, read_json, write_json};
+use roxmltree::{Document, Node, ParsingOptions};
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// Outcome of a single test case
+pub enum TestStatus {
+    #[default]
+    Passed,
+    Failed,
+    Error,
+    Skipped,
+}
+
+#[skip_serializing_none]
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// A single test case of a JUnit report
+pub struct TestCase {
+    pub name: String,
+    pub classname: Option<String>,
+    /// Duration in seconds
+    pub time: Option<f64>,
+    pub status: TestStatus,
+    /// Message attribute of the failure, error or skipped element
+    pub message: Option<String>,
+    /// Text of the failure or error element, usually the assertion and stack trace
+    pub failure: Option<String>,
+    pub system_out: Option<String>,
+    pub system_err: Option<String>,
+}
+
+impl TestCase {
+    /// Name of the test case including its class name, if any
+    pub fn full_name(&self) -> String {
+        match &self.classname {
+            Some(classname) if !classname.is_empty() => format!("{}.{}", classname, self.name),
+            _ => self.name.clone(),
+        }
+    }
+
+    /// Returns true if the test case failed or ended with an error
+    pub fn is_failed(&self) -> bool {
+        matches!(self.status, TestStatus::Failed | TestStatus::Error)
+    }
+}
+
+#[skip_serializing_none]
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// A test suite of a JUnit report
+pub struct TestSuite {
+    pub name: String,
+    /// Path of the JUnit file, relative to the artifact folder of the build
+    pub path: String,
+    /// Duration in seconds
+    pub time: Option<f64>,
+    pub cases: Vec<TestCase>,
+    pub system_out: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Test results of a build as stored in the data directory
+struct TestResults {
+    /// SHA-256 checksums of the JUnit reports the results were parsed from, by path relative to
+    /// the artifact folder of the build
+    reports: BTreeMap<String, String>,
+    suites: Vec<TestSuite>,
+}
+
+/// Returns true for JUnit reports as uploaded by Prow jobs, e.g. `junit_01.xml`
+///
+/// # Arguments
+///
+/// * `path` - Path of the artifact
+pub fn is_junit_file(path: &str) -> bool {
+    let name = Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    name.starts_with("junit") && name.ends_with(".xml")
+}
+
+fn child_text(node: Node, name: &str) -> Option<String> {
+    node.children()
+        .find(|child| child.has_tag_name(name))
+        .and_then(|child| child.text())
+        .map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty())
+}
+
+fn time(node: Node) -> Option<f64> {
+    node.attribute("time")
+        .and_then(|time| time.replace(',', "").parse().ok())
+}
+
+fn parse_case(node: Node) -> TestCase {
+    let mut case = TestCase {
+        name: node.attribute("name").unwrap_or_default().to_string(),
+        classname: node.attribute("classname").map(|name| name.to_string()),
+        time: time(node),
+        system_out: child_text(node, "system-out"),
+        system_err: child_text(node, "system-err"),
+        ..Default::default()
+    };
+    for (tag, status) in [
+        ("failure", TestStatus::Failed),
+        ("error", TestStatus::Error),
+        ("skipped", TestStatus::Skipped),
+    ] {
+        if let Some(child) = node.children().find(|child| child.has_tag_name(tag)) {
+            case.status = status;
+            case.message = child
+                .attribute("message")
+                .map(|message| message.to_string());
+            case.failure = child
+                .text()
+                .map(|text| text.trim().to_string())
+                .filter(|text| !text.is_empty());
+            break;
+        }
+    }
+    case
+}
+
+fn parse_suites(node: Node, path: &str, suites: &mut Vec<TestSuite>) {
+    if node.has_tag_name("testsuite") {
+        suites.push(TestSuite {
+            name: node.attribute("name").unwrap_or_default().to_string(),
+            path: path.to_string(),
+            time: time(node),
+            cases: node
+                .children()
+                .filter(|child| child.has_tag_name("testcase"))
+                .map(parse_case)
+                .collect(),
+            system_out: child_text(node, "system-out"),
+        });
+    }
+    for child in node
+        .children()
+        .filter(|child| child.has_tag_name("testsuite"))
+    {
+        parse_suites(child, path, suites);
+    }
+}
+
+/// Parses a JUnit report with either a `testsuites` or a `testsuite` root element
+///
+/// # Arguments
+///
+/// * `xml` - Content of the JUnit file
+/// * `path` - Path of the JUnit file, relative to the artifact folder of the build
+///
+/// # Returns
+///
+/// All test suites of the report, including nested ones
+pub fn parse_junit(xml: &str, path: &str) -> Result<Vec<TestSuite>> {
+    let options = ParsingOptions {
+        allow_dtd: true,
+        ..Default::default()
+    };
+    let document = Document::parse_with_options(xml, options).map_err(|e| Error::parse(path, e))?;
+    let mut suites = Vec::new();
+    parse_suites(document.root_element(), path, &mut suites);
+    Ok(suites)
+}
+
+/// Path of the file the test results of a build are stored in
+///
+/// # Arguments
+///
+/// * `build` - The build
+/// * `path` - The root data directory
+pub fn test_results_path(build: &BuildRecord, path: &str) -> String {
+    format!(
+        "{}tests/{}/{}.json",
+        check_slash(path),
+        build.instance,
+        build.build_id
+    )
+}
+
+/// Collects the test results of all JUnit reports of a build
+///
+/// The results are stored as `tests/<instance>/<build_id>.json` in the data directory together
+/// with the checksums of the reports, and read from there on subsequent calls unless reports
+/// have been added, removed or downloaded again since. Reports that cannot be parsed are skipped.
+///
+/// # Arguments
+///
+/// * `build` - The build whose artifacts have been downloaded
+/// * `path` - The root data directory
+///
+/// # Returns
+///
+/// The test suites of the build
+pub async fn collect_test_results(build: &BuildRecord, path: &str) -> Result<Vec<TestSuite>> {
+    let results_file = test_results_path(build, path);
+    // Results stored by earlier versions do not have checksums and are parsed again
+    let stored = match Path::new(&results_file).exists() {
+        true => read_json::<TestResults>(&results_file).ok(),
+        false => None,
+    };
+    let artifact_path = build.artifact_path(path);
+    if !Path::new(&artifact_path).exists() {
+        return Ok(stored.map(|stored| stored.suites).unwrap_or_default());
+    }
+    let manifest = Manifest::load(&artifact_path).unwrap_or_default();
+    let mut file_index = create_file_index(artifact_path.clone()).await;
+    file_index.retain(|file| is_junit_file(file));
+    file_index.sort();
+    let mut reports = BTreeMap::new();
+    for file in file_index {
+        let relative_path = Path::new(&file)
+            .strip_prefix(&artifact_path)
+            .map(|relative| relative.to_string_lossy().to_string())
+            .unwrap_or(file.clone());
+        let content = match std::fs::read(&file) {
+            Ok(content) => content,
+            Err(e) => {
+                println!("Skipping {}: {}", file, e);
+                continue;
+            }
+        };
+        let checksum = match manifest.files.get(&relative_path) {
+            Some(entry) => entry.sha256.clone(),
+            None => sha256(&content),
+        };
+        reports.insert(relative_path, (checksum, content));
+    }
+    let checksums = reports
+        .iter()
+        .map(|(path, (checksum, _))| (path.clone(), checksum.clone()))
+        .collect::<BTreeMap<String, String>>();
+    if let Some(stored) = stored.filter(|stored| stored.reports == checksums) {
+        return Ok(stored.suites);
+    }
+    let mut suites = Vec::new();
+    for (relative_path, (_, content)) in reports {
+        match parse_junit(&String::from_utf8_lossy(&content), &relative_path) {
+            Ok(mut file_suites) => suites.append(&mut file_suites),
+            Err(e) => println!("Skipping {}", e),
+        }
+    }
+    if let Some(tests_path) = Path::new(&results_file).parent() {
+        create_dir(&tests_path.to_string_lossy())?;
+    }
+    write_json(
+        &results_file,
+        &TestResults {
+            reports: checksums,
+            suites: suites.clone(),
+        },
+    )?;
+    Ok(suites)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Checks that suites, cases, durations, failures and output are extracted
+    fn test_parse_junit() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites>
+  <testsuite name="Kubernetes e2e suite" time="612.5">
+    <testcase name="[sig-network] DNS should resolve" classname="e2e" time="12.1">
+      <failure message="timed out" type="Failure">dns.go:42: timed out waiting for lookup</failure>
+      <system-out>lookup kubernetes.default</system-out>
+    </testcase>
+    <testcase name="[sig-node] Pods should start" classname="e2e" time="3"/>
+    <testcase name="[sig-storage] CSI" classname="e2e"><skipped message="no driver"/></testcase>
+  </testsuite>
+</testsuites>"#;
+        let suites = parse_junit(xml, "artifacts/junit_01.xml").unwrap();
+        assert_eq!(suites.len(), 1);
+        assert_eq!(suites[0].time, Some(612.5));
+        assert_eq!(suites[0].cases.len(), 3);
+        let failed = &suites[0].cases[0];
+        assert!(failed.is_failed());
+        assert_eq!(failed.full_name(), "e2e.[sig-network] DNS should resolve");
+        assert_eq!(failed.message.as_deref(), Some("timed out"));
+        assert_eq!(
+            failed.failure.as_deref(),
+            Some("dns.go:42: timed out waiting for lookup")
+        );
+        assert_eq!(
+            failed.system_out.as_deref(),
+            Some("lookup kubernetes.default")
+        );
+        assert_eq!(suites[0].cases[1].status, TestStatus::Passed);
+        assert_eq!(suites[0].cases[2].status, TestStatus::Skipped);
+        assert!(is_junit_file("artifacts/junit_01.xml"));
+        assert!(!is_junit_file("artifacts/build-log.txt"));
+    }
+
+    #[tokio::test]
+    /// Checks that stored test results are parsed again once a JUnit report has changed
+    async fn test_collect_test_results() {
+        let data_path = std::env::temp_dir()
+            .join(format!("arcalog-test-results-{}", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        let build = BuildRecord {
+            build_id: "1".to_string(),
+            source: "prow".to_string(),
+            instance: "prow.k8s.io".to_string(),
+            ..Default::default()
+        };
+        let artifact_path = build.artifact_path(&data_path);
+        create_dir(&artifact_path).unwrap();
+        let report = format!("{}/junit_01.xml", artifact_path);
+        std::fs::write(
+            &report,
+            r#"<testsuite name="e2e"><testcase name="a"><failure/></testcase></testsuite>"#,
+        )
+        .unwrap();
+        let suites = collect_test_results(&build, &data_path).await.unwrap();
+        assert!(suites[0].cases[0].is_failed());
+        assert!(Path::new(&test_results_path(&build, &data_path)).exists());
+
+        std::fs::write(
+            &report,
+            r#"<testsuite name="e2e"><testcase name="a"/></testsuite>"#,
+        )
+        .unwrap();
+        let suites = collect_test_results(&build, &data_path).await.unwrap();
+        std::fs::remove_dir_all(&data_path).unwrap();
+        assert!(!suites[0].cases[0].is_failed());
+    }
+}
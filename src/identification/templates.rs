@@ -0,0 +1,162 @@
+use super::{normalize_event, Event};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Placeholder for tokens that differ between the events of a template
+const WILDCARD: &str = "<*>";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Settings of the template mining
+pub struct TemplatesConfig {
+    /// Minimum share of equal tokens, between 0 and 1, for an event to be added to a template
+    #[serde(default = "TemplatesConfig::default_similarity")]
+    pub similarity: f64,
+}
+
+impl TemplatesConfig {
+    fn default_similarity() -> f64 {
+        0.5
+    }
+}
+
+impl Default for TemplatesConfig {
+    fn default() -> Self {
+        TemplatesConfig {
+            similarity: Self::default_similarity(),
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Location of an event belonging to a template
+pub struct Occurrence {
+    pub path: String,
+    pub line: usize,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Group of near-identical events
+pub struct Template {
+    /// Normalised event content in which differing tokens are replaced with `<*>`
+    pub template: String,
+    /// Number of events belonging to the template
+    pub count: usize,
+    pub first: Occurrence,
+    pub last: Occurrence,
+}
+
+/// Groups events into templates in the style of the Drain log parser
+///
+/// Events are normalised and split into tokens. An event is added to the most similar template
+/// with the same number of tokens if the share of equal tokens reaches the configured
+/// similarity, otherwise it starts a new template. Tokens that differ are replaced with `<*>`.
+///
+/// # Arguments
+///
+/// * `events` - The events in the order they were found
+/// * `config` - Template mining settings from the configuration file
+///
+/// # Returns
+///
+/// The templates ordered by the number of events, most frequent first
+pub fn mine_templates(events: &[Event], config: &TemplatesConfig) -> Vec<Template> {
+    let mut templates: Vec<(Vec<String>, Template)> = Vec::new();
+    // Indices into `templates` keyed by the number of tokens
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+
+    for event in events {
+        let tokens = normalize_event(&event.content)
+            .split(' ')
+            .map(|token| token.to_string())
+            .collect::<Vec<String>>();
+        let occurrence = Occurrence {
+            path: event.path.clone(),
+            line: event.line,
+        };
+        let group = groups.entry(tokens.len()).or_default();
+        let best = group
+            .iter()
+            .map(|index| (*index, similarity(&templates[*index].0, &tokens)))
+            .filter(|(_, similarity)| *similarity >= config.similarity)
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        match best {
+            Some((index, _)) => {
+                let (template_tokens, template) = &mut templates[index];
+                for (template_token, token) in template_tokens.iter_mut().zip(&tokens) {
+                    if template_token != token {
+                        *template_token = WILDCARD.to_string();
+                    }
+                }
+                template.template = template_tokens.join(" ");
+                template.count += 1;
+                template.last = occurrence;
+            }
+            None => {
+                group.push(templates.len());
+                templates.push((
+                    tokens.clone(),
+                    Template {
+                        template: tokens.join(" "),
+                        count: 1,
+                        first: occurrence.clone(),
+                        last: occurrence,
+                    },
+                ));
+            }
+        }
+    }
+
+    let mut templates = templates
+        .into_iter()
+        .map(|(_, template)| template)
+        .collect::<Vec<Template>>();
+    // The sort is stable, so templates with equal counts stay in the order they were found
+    templates.sort_by_key(|template| std::cmp::Reverse(template.count));
+    templates
+}
+
+/// Share of positions at which a template and an event have the same, non-wildcard token
+fn similarity(template: &[String], tokens: &[String]) -> f64 {
+    if tokens.is_empty() {
+        return 1.0;
+    }
+    let equal = template
+        .iter()
+        .zip(tokens)
+        .filter(|(template_token, token)| *template_token != WILDCARD && template_token == token)
+        .count();
+    equal as f64 / tokens.len() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Checks that events differing in single tokens are merged into one template with wildcards
+    fn test_mine_templates() {
+        let events = [
+            "error: pod api-7f9c deleted",
+            "warning: disk almost full",
+            "error: pod web-1b2d deleted",
+            "error: pod db-0a9e deleted",
+        ]
+        .iter()
+        .enumerate()
+        .map(|(index, content)| Event {
+            path: "build-log.txt".to_string(),
+            line: index + 1,
+            content: content.to_string(),
+            ..Default::default()
+        })
+        .collect::<Vec<Event>>();
+        let templates = mine_templates(&events, &TemplatesConfig::default());
+        assert_eq!(templates.len(), 2);
+        assert_eq!(templates[0].template, "error: pod <*> deleted");
+        assert_eq!(templates[0].count, 3);
+        assert_eq!(templates[0].first.line, 1);
+        assert_eq!(templates[0].last.line, 4);
+        assert_eq!(templates[1].template, "warning: disk almost full");
+        assert_eq!(templates[1].count, 1);
+    }
+}
@@ -0,0 +1,664 @@
+use crate::collection::{instance_name, BuildRecord, BuildState};
+use crate::error::{Error, Result};
+use crate::system::{check_slash, create_dir};
+use rusqlite::types::{FromSql, FromSqlResult, ToSqlOutput, ValueRef};
+use rusqlite::{params, Connection, OptionalExtension, Row, ToSql, Transaction};
+use serde::{Deserialize, Serialize};
+
+/// Current version of the index schema, stored in the SQLite `user_version` pragma
+const SCHEMA_VERSION: i64 = 4;
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// A version of a job as listed by a collector, identified by its unique ID and resource version
+pub struct JobVersion {
+    pub uid: String,
+    pub resource_version: String,
+    pub build_id: Option<String>,
+    pub state: BuildState,
+    /// The job in the collector's own format
+    pub content: serde_json::Value,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// A change of the state of a job between two collections
+pub struct Transition {
+    pub uid: String,
+    pub build_id: Option<String>,
+    /// The previously stored state, or `None` if the job was seen for the first time
+    pub from_state: Option<BuildState>,
+    pub to_state: BuildState,
+    /// When the change was observed, in RFC 3339 format
+    pub observed: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq)]
+/// Outcome of storing a list of jobs
+pub struct StoredJobs {
+    /// Unique IDs of the jobs that were new or had a new resource version
+    pub updated: Vec<String>,
+    /// Number of jobs that were already stored with the same resource version
+    pub unchanged: usize,
+    pub transitions: Vec<Transition>,
+}
+
+/// Embedded SQLite index of all collected builds
+///
+/// The index is stored as `index.sqlite` in the root of the data directory and replaces scanning
+/// every stored metadata snapshot when looking up builds.
+pub struct BuildIndex {
+    connection: Connection,
+    previous_version: i64,
+}
+
+impl BuildIndex {
+    /// Opens the index in the given data directory, creating it if it does not exist yet
+    ///
+    /// # Arguments
+    ///
+    /// * `data_path` - The root data directory
+    pub fn open(data_path: &str) -> Result<Self> {
+        let data_path = check_slash(data_path);
+        create_dir(&data_path)?;
+        let connection = Connection::open(format!("{}index.sqlite", data_path))?;
+        connection.busy_timeout(std::time::Duration::from_secs(10))?;
+        let mut index = BuildIndex {
+            connection,
+            previous_version: 0,
+        };
+        index.migrate()?;
+        Ok(index)
+    }
+
+    /// Opens a temporary index that only lives in memory
+    pub fn open_in_memory() -> Result<Self> {
+        let mut index = BuildIndex {
+            connection: Connection::open_in_memory()?,
+            previous_version: 0,
+        };
+        index.migrate()?;
+        Ok(index)
+    }
+
+    /// Returns true if the index did not exist before it was opened
+    pub fn is_created(&self) -> bool {
+        self.previous_version == 0
+    }
+
+    /// Returns the schema version the index had before it was opened, which is 0 if it did not exist
+    pub fn previous_version(&self) -> i64 {
+        self.previous_version
+    }
+
+    fn migrate(&mut self) -> Result<()> {
+        let version: i64 = self
+            .connection
+            .query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        self.previous_version = version;
+        if version < 1 {
+            self.connection.execute_batch(
+                "CREATE TABLE IF NOT EXISTS builds (
+                    build_id TEXT PRIMARY KEY,
+                    job TEXT NOT NULL,
+                    state TEXT NOT NULL,
+                    url TEXT NOT NULL,
+                    start_time TEXT,
+                    completion_time TEXT,
+                    refs TEXT,
+                    source TEXT NOT NULL
+                );
+                CREATE INDEX IF NOT EXISTS builds_job ON builds (job);",
+            )?;
+        }
+        if version < 2 {
+            self.connection.execute_batch(
+                "ALTER TABLE builds ADD COLUMN duration INTEGER;
+                ALTER TABLE builds ADD COLUMN result TEXT;
+                ALTER TABLE builds ADD COLUMN revision TEXT;
+                ALTER TABLE builds ADD COLUMN node TEXT;",
+            )?;
+        }
+        if version < 3 {
+            self.connection.execute_batch(
+                "CREATE TABLE IF NOT EXISTS jobs (
+                    source TEXT NOT NULL,
+                    uid TEXT NOT NULL,
+                    resource_version TEXT NOT NULL,
+                    build_id TEXT,
+                    state TEXT NOT NULL,
+                    content TEXT NOT NULL,
+                    PRIMARY KEY (source, uid)
+                );
+                CREATE TABLE IF NOT EXISTS transitions (
+                    source TEXT NOT NULL,
+                    uid TEXT NOT NULL,
+                    build_id TEXT,
+                    from_state TEXT,
+                    to_state TEXT NOT NULL,
+                    observed TEXT NOT NULL
+                );
+                CREATE INDEX IF NOT EXISTS transitions_build_id ON transitions (build_id);",
+            )?;
+        }
+        if version < 4 {
+            // Builds and jobs are keyed by the collector instance they were collected from
+            let transaction = self.connection.transaction()?;
+            transaction.execute_batch(
+                "ALTER TABLE builds RENAME TO builds_v3;
+                CREATE TABLE builds (
+                    instance TEXT NOT NULL,
+                    build_id TEXT NOT NULL,
+                    job TEXT NOT NULL,
+                    state TEXT NOT NULL,
+                    url TEXT NOT NULL,
+                    start_time TEXT,
+                    completion_time TEXT,
+                    refs TEXT,
+                    source TEXT NOT NULL,
+                    duration INTEGER,
+                    result TEXT,
+                    revision TEXT,
+                    node TEXT,
+                    PRIMARY KEY (instance, build_id)
+                );
+                INSERT INTO builds
+                    SELECT '', build_id, job, state, url, start_time, completion_time, refs, source,
+                        duration, result, revision, node
+                    FROM builds_v3;
+                DROP TABLE builds_v3;
+                CREATE INDEX builds_job ON builds (job);
+                CREATE INDEX builds_build_id ON builds (build_id);
+                ALTER TABLE jobs RENAME TO jobs_v3;
+                CREATE TABLE jobs (
+                    source TEXT NOT NULL,
+                    instance TEXT NOT NULL,
+                    uid TEXT NOT NULL,
+                    resource_version TEXT NOT NULL,
+                    build_id TEXT,
+                    state TEXT NOT NULL,
+                    content TEXT NOT NULL,
+                    PRIMARY KEY (source, instance, uid)
+                );
+                INSERT INTO jobs
+                    SELECT source, '', uid, resource_version, build_id, state, content FROM jobs_v3;
+                DROP TABLE jobs_v3;
+                ALTER TABLE transitions ADD COLUMN instance TEXT NOT NULL DEFAULT '';",
+            )?;
+            assign_instances(&transaction)?;
+            transaction.pragma_update(None, "user_version", 4)?;
+            transaction.commit()?;
+        }
+        self.connection
+            .pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        Ok(())
+    }
+
+    /// Inserts the given builds, replacing previously stored versions of the same builds
+    ///
+    /// Build details that are missing from a new version, like the duration, are kept from the
+    /// previously stored version.
+    ///
+    /// # Arguments
+    ///
+    /// * `builds` - The builds to store
+    pub fn upsert(&mut self, builds: &[BuildRecord]) -> Result<()> {
+        let transaction = self.connection.transaction()?;
+        {
+            let mut statement = transaction.prepare_cached(
+                "INSERT INTO builds
+                    (build_id, job, state, url, start_time, completion_time, refs, source,
+                     duration, result, revision, node, instance)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+                 ON CONFLICT (instance, build_id) DO UPDATE SET
+                    job = excluded.job,
+                    state = excluded.state,
+                    url = excluded.url,
+                    start_time = excluded.start_time,
+                    completion_time = excluded.completion_time,
+                    refs = excluded.refs,
+                    source = excluded.source,
+                    duration = COALESCE(excluded.duration, builds.duration),
+                    result = COALESCE(excluded.result, builds.result),
+                    revision = COALESCE(excluded.revision, builds.revision),
+                    node = COALESCE(excluded.node, builds.node)",
+            )?;
+            for build in builds {
+                let refs = build
+                    .refs
+                    .as_ref()
+                    .map(serde_json::to_string)
+                    .transpose()
+                    .map_err(|e| Error::parse("build refs", e))?;
+                statement.execute(params![
+                    build.build_id,
+                    build.job,
+                    build.state,
+                    build.url,
+                    build.start_time,
+                    build.completion_time,
+                    refs,
+                    build.source,
+                    build.duration,
+                    build.result,
+                    build.revision,
+                    build.node,
+                    build.instance,
+                ])?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Looks up a single build
+    ///
+    /// # Arguments
+    ///
+    /// * `instance` - The instance the build was collected from
+    /// * `build_id` - The requested build ID
+    ///
+    /// # Returns
+    ///
+    /// `BuildRecord` of the build, if it is part of the index
+    pub fn get(&self, instance: &str, build_id: &str) -> Result<Option<BuildRecord>> {
+        let build = self
+            .connection
+            .query_row(
+                "SELECT build_id, job, state, url, start_time, completion_time, refs, source,
+                    duration, result, revision, node, instance
+                 FROM builds WHERE instance = ?1 AND build_id = ?2",
+                params![instance, build_id],
+                build_from_row,
+            )
+            .optional()?;
+        Ok(build)
+    }
+
+    /// Looks up the builds with the given ID in all instances
+    ///
+    /// # Arguments
+    ///
+    /// * `build_id` - The requested build ID
+    ///
+    /// # Returns
+    ///
+    /// `BuildRecord` of every matching build, ordered by instance
+    pub fn find(&self, build_id: &str) -> Result<Vec<BuildRecord>> {
+        let mut statement = self.connection.prepare_cached(
+            "SELECT build_id, job, state, url, start_time, completion_time, refs, source,
+                duration, result, revision, node, instance
+             FROM builds WHERE build_id = ?1 ORDER BY instance",
+        )?;
+        let builds = statement
+            .query_map(params![build_id], build_from_row)?
+            .collect::<rusqlite::Result<Vec<BuildRecord>>>()?;
+        Ok(builds)
+    }
+
+    /// Lists builds ordered by start time
+    ///
+    /// # Arguments
+    ///
+    /// * `since` - If set, only builds started at or after this RFC 3339 timestamp are listed
+    /// * `states` - If not empty, only builds in one of these states are listed
+    ///
+    /// # Returns
+    ///
+    /// `BuildRecord` of every matching build
+    pub fn list(&self, since: Option<&str>, states: &[BuildState]) -> Result<Vec<BuildRecord>> {
+        let mut statement = self.connection.prepare_cached(
+            "SELECT build_id, job, state, url, start_time, completion_time, refs, source,
+                duration, result, revision, node, instance
+             FROM builds WHERE ?1 IS NULL OR start_time >= ?1
+             ORDER BY start_time, instance, build_id",
+        )?;
+        let mut builds = statement
+            .query_map(params![since], build_from_row)?
+            .collect::<rusqlite::Result<Vec<BuildRecord>>>()?;
+        builds.retain(|build| states.is_empty() || states.contains(&build.state));
+        Ok(builds)
+    }
+
+    /// Stores the jobs that are new or have a new resource version and records their state changes
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - Name of the collector that listed the jobs
+    /// * `instance` - The collector instance that listed the jobs
+    /// * `jobs` - The jobs as currently listed by the collector
+    /// * `observed` - When the jobs were listed, in RFC 3339 format
+    ///
+    /// # Returns
+    ///
+    /// `StoredJobs` describing which jobs were updated and which states changed
+    pub fn store_jobs(
+        &mut self,
+        source: &str,
+        instance: &str,
+        jobs: &[JobVersion],
+        observed: &str,
+    ) -> Result<StoredJobs> {
+        let mut stored = StoredJobs::default();
+        let transaction = self.connection.transaction()?;
+        {
+            let mut select = transaction.prepare_cached(
+                "SELECT resource_version, state FROM jobs
+                 WHERE source = ?1 AND instance = ?2 AND uid = ?3",
+            )?;
+            let mut upsert = transaction.prepare_cached(
+                "INSERT OR REPLACE INTO jobs
+                    (source, instance, uid, resource_version, build_id, state, content)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )?;
+            let mut insert_transition = transaction.prepare_cached(
+                "INSERT INTO transitions
+                    (source, instance, uid, build_id, from_state, to_state, observed)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            )?;
+            for job in jobs {
+                let previous: Option<(String, BuildState)> = select
+                    .query_row(params![source, instance, job.uid], |row| {
+                        Ok((row.get(0)?, row.get(1)?))
+                    })
+                    .optional()?;
+                if let Some((resource_version, _)) = &previous {
+                    if *resource_version == job.resource_version {
+                        stored.unchanged += 1;
+                        continue;
+                    }
+                }
+                upsert.execute(params![
+                    source,
+                    instance,
+                    job.uid,
+                    job.resource_version,
+                    job.build_id,
+                    job.state,
+                    job.content.to_string(),
+                ])?;
+                stored.updated.push(job.uid.clone());
+                let from_state = previous.map(|(_, state)| state);
+                if from_state.as_ref() != Some(&job.state) {
+                    insert_transition.execute(params![
+                        source,
+                        instance,
+                        job.uid,
+                        job.build_id,
+                        from_state,
+                        job.state,
+                        observed,
+                    ])?;
+                    stored.transitions.push(Transition {
+                        uid: job.uid.clone(),
+                        build_id: job.build_id.clone(),
+                        from_state,
+                        to_state: job.state.clone(),
+                        observed: observed.to_string(),
+                    });
+                }
+            }
+        }
+        transaction.commit()?;
+        Ok(stored)
+    }
+
+    /// Lists the recorded state changes of a build in the order they were observed
+    ///
+    /// # Arguments
+    ///
+    /// * `instance` - The instance the build was collected from
+    /// * `build_id` - The requested build ID
+    pub fn transitions(&self, instance: &str, build_id: &str) -> Result<Vec<Transition>> {
+        let mut statement = self.connection.prepare_cached(
+            "SELECT uid, build_id, from_state, to_state, observed FROM transitions
+             WHERE instance = ?1 AND build_id = ?2 ORDER BY observed, rowid",
+        )?;
+        let transitions = statement
+            .query_map(params![instance, build_id], |row| {
+                Ok(Transition {
+                    uid: row.get(0)?,
+                    build_id: row.get(1)?,
+                    from_state: row.get(2)?,
+                    to_state: row.get(3)?,
+                    observed: row.get(4)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<Transition>>>()?;
+        Ok(transitions)
+    }
+
+    /// Returns the most recently stored job of a build
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - Name of the collector that stored the job
+    /// * `instance` - The instance the build was collected from
+    /// * `build_id` - The requested build ID
+    ///
+    /// # Returns
+    ///
+    /// The job as stored by the collector, or `None` if no job of the build has been stored
+    pub fn job(&self, source: &str, instance: &str, build_id: &str) -> Result<Option<String>> {
+        let content = self
+            .connection
+            .query_row(
+                "SELECT content FROM jobs
+                 WHERE source = ?1 AND instance = ?2 AND build_id = ?3
+                 ORDER BY rowid DESC LIMIT 1",
+                params![source, instance, build_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(content)
+    }
+
+    /// Counts the builds in the index
+    pub fn count(&self) -> Result<usize> {
+        let count: i64 = self
+            .connection
+            .query_row("SELECT COUNT(*) FROM builds", [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+}
+
+impl ToSql for BuildState {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for BuildState {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        String::column_result(value).map(BuildState::from)
+    }
+}
+
+fn build_from_row(row: &Row) -> rusqlite::Result<BuildRecord> {
+    let refs: Option<String> = row.get(6)?;
+    Ok(BuildRecord {
+        build_id: row.get(0)?,
+        job: row.get(1)?,
+        state: row.get(2)?,
+        url: row.get(3)?,
+        start_time: row.get(4)?,
+        completion_time: row.get(5)?,
+        refs: refs.and_then(|refs| serde_json::from_str(&refs).ok()),
+        source: row.get(7)?,
+        duration: row.get(8)?,
+        result: row.get(9)?,
+        revision: row.get(10)?,
+        node: row.get(11)?,
+        instance: row.get(12)?,
+    })
+}
+
+/// Assigns builds, jobs and state changes stored by earlier versions to the instance named after
+/// the host of their URL
+fn assign_instances(transaction: &Transaction) -> Result<()> {
+    let builds = transaction
+        .prepare("SELECT rowid, url FROM builds")?
+        .query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<(i64, String)>>>()?;
+    let mut update = transaction.prepare("UPDATE builds SET instance = ?1 WHERE rowid = ?2")?;
+    for (rowid, url) in builds {
+        update.execute(params![instance_name(&url), rowid])?;
+    }
+    let jobs = transaction
+        .prepare("SELECT rowid, content FROM jobs")?
+        .query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<(i64, String)>>>()?;
+    let mut update = transaction.prepare("UPDATE jobs SET instance = ?1 WHERE rowid = ?2")?;
+    for (rowid, content) in jobs {
+        let url = serde_json::from_str::<serde_json::Value>(&content)
+            .ok()
+            .and_then(|job| job["status"]["url"].as_str().map(instance_name))
+            .unwrap_or_default();
+        update.execute(params![url, rowid])?;
+    }
+    transaction.execute(
+        "UPDATE transitions SET instance = COALESCE(
+            (SELECT instance FROM jobs WHERE jobs.source = transitions.source AND jobs.uid = transitions.uid),
+            '')",
+        [],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    /// Checks that upserting a build twice keeps only the latest version, but keeps known details
+    fn test_upsert_and_get() {
+        let mut index = BuildIndex::open_in_memory().unwrap();
+        let mut build = BuildRecord {
+            build_id: "1".to_string(),
+            url: "https://prow.example.com/view/1".to_string(),
+            job: "e2e".to_string(),
+            state: BuildState::Pending,
+            source: "prow".to_string(),
+            instance: "prow.example.com".to_string(),
+            refs: Some(serde_json::json!({"org": "arcalot"})),
+            ..Default::default()
+        };
+        index
+            .upsert(&[BuildRecord {
+                duration: Some(42),
+                ..build.clone()
+            }])
+            .unwrap();
+        build.state = BuildState::Failure;
+        index.upsert(&[build.clone()]).unwrap();
+        build.duration = Some(42);
+        assert_eq!(index.count().unwrap(), 1);
+        assert_eq!(
+            index.get("prow.example.com", "1").unwrap(),
+            Some(build.clone())
+        );
+        assert_eq!(index.get("prow.example.com", "2").unwrap(), None);
+        assert_eq!(index.list(None, &[]).unwrap(), vec![build.clone()]);
+        assert!(index.list(None, &[BuildState::Error]).unwrap().is_empty());
+
+        // The same build ID may be collected from another instance
+        let other = BuildRecord {
+            instance: "prow.internal".to_string(),
+            ..build.clone()
+        };
+        index.upsert(std::slice::from_ref(&other)).unwrap();
+        assert_eq!(index.find("1").unwrap(), vec![build, other]);
+    }
+
+    #[test]
+    /// Checks that only new or changed jobs are stored and state changes are recorded
+    fn test_store_jobs() {
+        let mut index = BuildIndex::open_in_memory().unwrap();
+        let mut job = JobVersion {
+            uid: "a".to_string(),
+            resource_version: "1".to_string(),
+            build_id: Some("1".to_string()),
+            state: BuildState::Pending,
+            content: serde_json::json!({}),
+        };
+        let stored = index.store_jobs("prow", "a", &[job.clone()], "t1").unwrap();
+        assert_eq!(stored.updated, vec!["a"]);
+        let stored = index.store_jobs("prow", "a", &[job.clone()], "t2").unwrap();
+        assert!(stored.updated.is_empty());
+        assert_eq!(stored.unchanged, 1);
+        job.resource_version = "2".to_string();
+        job.state = BuildState::Failure;
+        index.store_jobs("prow", "a", &[job.clone()], "t3").unwrap();
+        // Jobs of other instances are stored separately
+        let stored = index.store_jobs("prow", "b", &[job], "t3").unwrap();
+        assert_eq!(stored.updated, vec!["a"]);
+        let transitions = index
+            .transitions("a", "1")
+            .unwrap()
+            .into_iter()
+            .map(|transition| (transition.from_state, transition.to_state))
+            .collect::<Vec<(Option<BuildState>, BuildState)>>();
+        assert_eq!(
+            transitions,
+            vec![
+                (None, BuildState::Pending),
+                (Some(BuildState::Pending), BuildState::Failure)
+            ]
+        );
+        assert_eq!(index.job("prow", "a", "1").unwrap().as_deref(), Some("{}"));
+        assert_eq!(index.job("prow", "c", "1").unwrap(), None);
+    }
+
+    #[test]
+    /// Checks that builds, jobs and state changes of a version 3 index are assigned to instances
+    fn test_migrate_instances() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE builds (
+                    build_id TEXT PRIMARY KEY, job TEXT NOT NULL, state TEXT NOT NULL,
+                    url TEXT NOT NULL, start_time TEXT, completion_time TEXT, refs TEXT,
+                    source TEXT NOT NULL, duration INTEGER, result TEXT, revision TEXT, node TEXT
+                );
+                CREATE INDEX builds_job ON builds (job);
+                CREATE TABLE jobs (
+                    source TEXT NOT NULL, uid TEXT NOT NULL, resource_version TEXT NOT NULL,
+                    build_id TEXT, state TEXT NOT NULL, content TEXT NOT NULL,
+                    PRIMARY KEY (source, uid)
+                );
+                CREATE TABLE transitions (
+                    source TEXT NOT NULL, uid TEXT NOT NULL, build_id TEXT, from_state TEXT,
+                    to_state TEXT NOT NULL, observed TEXT NOT NULL
+                );
+                INSERT INTO builds (build_id, job, state, url, source)
+                    VALUES ('1', 'e2e', 'failure', 'https://prow.k8s.io/view/1', 'prow');
+                INSERT INTO jobs VALUES
+                    ('prow', 'a', '1', '1', 'failure', '{\"status\": {\"url\": \"https://prow.k8s.io/view/1\"}}');
+                INSERT INTO transitions VALUES ('prow', 'a', '1', NULL, 'failure', 't1');
+                PRAGMA user_version = 3;",
+            )
+            .unwrap();
+        let mut index = BuildIndex {
+            connection,
+            previous_version: 0,
+        };
+        index.migrate().unwrap();
+        assert_eq!(index.previous_version(), 3);
+        let build = index.get("prow.k8s.io", "1").unwrap().unwrap();
+        assert_eq!(build.job, "e2e");
+        assert_eq!(index.transitions("prow.k8s.io", "1").unwrap().len(), 1);
+        let job = JobVersion {
+            uid: "a".to_string(),
+            resource_version: "1".to_string(),
+            build_id: Some("1".to_string()),
+            state: BuildState::Failure,
+            content: serde_json::json!({}),
+        };
+        let stored = index
+            .store_jobs("prow", "prow.k8s.io", &[job], "t2")
+            .unwrap();
+        assert_eq!(stored.unchanged, 1);
+    }
+}
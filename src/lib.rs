@@ -4,8 +4,13 @@
 
 pub mod analysis;
 pub mod collection;
+pub mod config;
+pub mod error;
 pub mod identification;
+pub mod index;
 pub mod system;
 
+pub use error::{Error, Result};
+
 #[cfg(test)]
 mod tests {}
@@ -1,38 +1,55 @@
-use arcalog::{collection::prow::*, system::check_slash};
+use arcalog::{
+    analysis::{
+        compare::{compare_builds, BuildComparison},
+        flakes::{flake_report, FlakeReport},
+        labelling::{save_label, train_from_labels, HumanLabel},
+        Labels,
+    },
+    collection::{download, prow::*, split_build_ref, BuildRecord, BuildState, Registry},
+    config::Config,
+    system::check_slash,
+    Error,
+};
 use axum::{
-    extract::Query,
+    extract::{Path, Query, State},
     http::StatusCode,
     response::{Html, IntoResponse, Json},
-    routing::get,
+    routing::{get, post},
     Router,
 };
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use serde::{Deserialize, Serialize};
 use serde_with::*;
-use serde_yaml;
-use std::fs::File;
+use std::sync::Arc;
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-struct Root {
-    collection: Option<Collection>,
-    data: Option<String>,
+/// Shared state of the web server
+struct AppState {
+    data_path: String,
+    config: Config,
 }
 
+#[skip_serializing_none]
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-struct Collection {
-    prow: Option<Prow>,
+struct BuildId {
+    /// ID of the build, optionally prefixed with its instance as in `<instance>/<build_id>`
+    build_id: String,
+    /// Prow instance the build was collected from, only needed if several instances have a build with this ID
+    instance: Option<String>,
 }
 
+#[skip_serializing_none]
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-struct Prow {
-    location: Vec<String>,
-    parameters: Option<String>,
+struct BuildsQuery {
+    /// Comma-separated list of states, e.g. `failure,error`
+    state: Option<String>,
+    days: Option<u32>,
 }
 
 #[skip_serializing_none]
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-struct BuildId {
-    build_id: String,
+struct BuildsResponse {
+    builds: Vec<BuildRecord>,
+    error: Option<String>,
 }
 
 #[skip_serializing_none]
@@ -41,6 +58,30 @@ struct BuildComparisons {
     list_of_builds: String,
 }
 
+#[skip_serializing_none]
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct FlakesWindow {
+    days: Option<u32>,
+}
+
+#[skip_serializing_none]
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct LabelRequest {
+    label: String,
+    /// Prow instance the build was collected from, only needed if several instances have a build with this ID
+    instance: Option<String>,
+    author: Option<String>,
+    note: Option<String>,
+    issue: Option<String>,
+}
+
+#[skip_serializing_none]
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct LabelResponse {
+    label: Option<HumanLabel>,
+    error: Option<String>,
+}
+
 #[derive(Parser, Debug)]
 #[clap(
     name = "   __    ____   ___    __    __    _____  ___ 
@@ -68,6 +109,50 @@ struct Args {
     /// Starts the Arcalog web server
     #[clap(long, value_parser)]
     http: bool,
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Records the root cause of a collected build
+    Label {
+        /// ID of the build to label, prefixed with its instance as in `<instance>/<build_id>` if several instances have a build with this ID
+        build_id: String,
+        /// One of transient, environment, code, dependency or unknown
+        label: String,
+        /// Free-text explanation of the root cause
+        #[clap(long, value_parser)]
+        note: Option<String>,
+        /// URL of the issue tracking the root cause
+        #[clap(long, value_parser)]
+        issue: Option<String>,
+        /// Who recorded the label, defaults to the current user
+        #[clap(long, value_parser)]
+        author: Option<String>,
+    },
+    /// Trains the failure classifier on all labelled builds
+    Train,
+    /// Lists jobs that both passed and failed on the same commit
+    Flakes {
+        /// Number of days to look back, defaults to the window in your config file
+        #[clap(long, value_parser)]
+        days: Option<u32>,
+    },
+    /// Lists the collected builds
+    Builds {
+        /// Comma-separated list of states to list, e.g. `failure,error`, defaults to all states
+        #[clap(long, value_parser)]
+        state: Option<String>,
+        /// Number of days to look back, defaults to all collected builds
+        #[clap(long, value_parser)]
+        days: Option<u32>,
+    },
+    /// Checks downloaded artifacts and downloads corrupt, incomplete or missing ones again
+    Verify {
+        /// ID of the build to verify, optionally prefixed with its instance as in `<instance>/<build_id>`, defaults to all builds with downloaded artifacts
+        build_id: Option<String>,
+    },
 }
 
 async fn handler_404() -> impl IntoResponse {
@@ -81,17 +166,152 @@ async fn handler() -> Html<&'static str> {
     Html(include_str!("../static/index.html"))
 }
 
-async fn handler_api_build(build_info: Query<BuildId>, source_path: String) -> Json<BuildInfo> {
-    let build_info = get_build_info(build_info.build_id.to_string(), source_path).await;
-    return Json(build_info);
+/// Maps library errors to the HTTP status code returned by the API
+fn error_status(error: &Error) -> StatusCode {
+    match error {
+        Error::NotFound(_) => StatusCode::NOT_FOUND,
+        Error::InvalidInput(_) => StatusCode::BAD_REQUEST,
+        Error::Network { .. } => StatusCode::BAD_GATEWAY,
+        Error::Parse { .. } | Error::Io { .. } | Error::Database(_) => {
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+async fn handler_api_build(
+    State(state): State<Arc<AppState>>,
+    build_info: Query<BuildId>,
+) -> (StatusCode, Json<BuildInfo>) {
+    let (instance, build_id) = split_build_ref(&build_info.build_id);
+    let instance = build_info
+        .instance
+        .clone()
+        .or(instance.map(str::to_string))
+        .filter(|instance| !instance.is_empty());
+    let build_id = build_id.to_string();
+    match get_build_info(
+        build_id.clone(),
+        instance.clone(),
+        state.data_path.clone(),
+        &state.config,
+    )
+    .await
+    {
+        Ok(build_info) => (StatusCode::OK, Json(build_info)),
+        Err(e) => (
+            error_status(&e),
+            Json(BuildInfo {
+                build_id,
+                instance,
+                error: Some(e.to_string()),
+                ..Default::default()
+            }),
+        ),
+    }
+}
+
+/// Parses a comma-separated list of build states
+fn parse_states(states: Option<&str>) -> Vec<BuildState> {
+    states
+        .unwrap_or_default()
+        .split(',')
+        .map(|state| state.trim())
+        .filter(|state| !state.is_empty())
+        .map(BuildState::from)
+        .collect()
+}
+
+async fn handler_api_builds(
+    State(state): State<Arc<AppState>>,
+    query: Query<BuildsQuery>,
+) -> (StatusCode, Json<BuildsResponse>) {
+    let states = parse_states(query.state.as_deref());
+    match list_builds(&state.data_path, query.days, &states).await {
+        Ok(builds) => (
+            StatusCode::OK,
+            Json(BuildsResponse {
+                builds,
+                error: None,
+            }),
+        ),
+        Err(e) => (
+            error_status(&e),
+            Json(BuildsResponse {
+                builds: Vec::new(),
+                error: Some(e.to_string()),
+            }),
+        ),
+    }
 }
 
-async fn handler_api_compare(build_info: Query<BuildComparisons>) -> Html<String> {
-    let comparison_list = build_info.list_of_builds.split(",");
-    Html(format!(
-        "Going to implement comparison of analysis results between several build IDs. You queried these IDs: {}!",
-        comparison_list.collect::<Vec<&str>>().join(", ")
-    ))
+async fn handler_api_compare(
+    State(state): State<Arc<AppState>>,
+    build_info: Query<BuildComparisons>,
+) -> Json<BuildComparison> {
+    let comparison_list = build_info
+        .list_of_builds
+        .split(',')
+        .map(|build_id| build_id.to_string())
+        .collect::<Vec<String>>();
+    Json(compare_builds(comparison_list, state.data_path.clone(), &state.config).await)
+}
+
+async fn handler_api_label(
+    State(state): State<Arc<AppState>>,
+    Path(build_id): Path<String>,
+    Json(request): Json<LabelRequest>,
+) -> (StatusCode, Json<LabelResponse>) {
+    let result = match request.label.parse::<Labels>() {
+        Ok(label) => {
+            let label = HumanLabel {
+                build_id,
+                instance: request.instance.unwrap_or_default(),
+                label,
+                author: request.author,
+                timestamp: String::new(),
+                note: request.note,
+                issue: request.issue,
+            };
+            save_label(&state.data_path, label).await
+        }
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(label) => (
+            StatusCode::OK,
+            Json(LabelResponse {
+                label: Some(label),
+                error: None,
+            }),
+        ),
+        Err(e) => (
+            error_status(&e),
+            Json(LabelResponse {
+                label: None,
+                error: Some(e.to_string()),
+            }),
+        ),
+    }
+}
+
+async fn handler_api_flakes(
+    State(state): State<Arc<AppState>>,
+    window: Query<FlakesWindow>,
+) -> (StatusCode, Json<FlakeReport>) {
+    let days = window
+        .days
+        .unwrap_or(state.config.analysis.flakes.window_days);
+    match flake_report(&state.data_path, days).await {
+        Ok(report) => (StatusCode::OK, Json(report)),
+        Err(e) => (
+            error_status(&e),
+            Json(FlakeReport {
+                window_days: days,
+                error: Some(e.to_string()),
+                ..Default::default()
+            }),
+        ),
+    }
 }
 
 async fn handler_build_id() -> impl IntoResponse {
@@ -107,58 +327,223 @@ async fn main() {
     let artifacts_collection = args.artifacts;
     let http_server = args.http;
     let config_location = args.config;
-    let config = File::open(config_location).expect("Could not open config file");
-    let config: Root = serde_yaml::from_reader(config).expect("Could not parse config file");
+    let config = Config::from_file(&config_location).expect("Could not load config file");
+    download::configure(config.download.clone()).expect("Could not set up downloads");
     let data_path_from_args = args.data;
     let collect = args.collect;
-    let data_path_from_cfg = config.data.unwrap_or("".to_string());
+    let data_path_from_cfg = config.data.clone().unwrap_or("".to_string());
     let mut data_path = String::from("data/");
     if data_path_from_args != "data/" {
         data_path = check_slash(&data_path_from_args);
-    } else if data_path_from_cfg != "" {
+    } else if !data_path_from_cfg.is_empty() {
         data_path = check_slash(&data_path_from_cfg);
     }
 
-    if collect != "" && config.collection.is_some() {
+    let collection = config.collection.clone().unwrap_or_default();
+    if !collect.is_empty() {
+        let registry = Registry::default();
         let collectsplit = collect.split(",");
         let collectargs: Vec<&str> = collectsplit.collect();
-        let collection = config.collection.as_ref().unwrap();
 
         for source in collectargs {
-            match source {
-                "prow" => {
-                    if collection.prow.is_some() {
-                        let prow = collection.prow.as_ref().unwrap();
-                        let location = prow
-                            .location
-                            .iter()
-                            .map(|s| s.as_str())
-                            .collect::<Vec<&str>>();
-
-                        for item in location {
-                            if !item.is_empty() {
-                                download_metadata(item, &data_path, artifacts_collection).await;
-                            }
-                        }
+            let Some(source_config) = collection.get(source) else {
+                println!("{} is not a valid source", source);
+                continue;
+            };
+            match registry.create(source, source_config) {
+                Some(Ok(collector)) => {
+                    if let Err(e) = collector
+                        .download_metadata(&data_path, artifacts_collection)
+                        .await
+                    {
+                        println!(
+                            "⛔\t\x1b[93m\x1b[1mCollection from {} failed: {}\x1b[0m",
+                            source, e
+                        );
                     }
                 }
-                &_ => {
+                Some(Err(e)) => {
+                    println!("Invalid configuration for {}: {}", source, e);
+                }
+                None => {
                     println!("{} is not a valid source", source);
                 }
             }
         }
     }
 
+    match args.command {
+        Some(Command::Label {
+            build_id,
+            label,
+            note,
+            issue,
+            author,
+        }) => {
+            let result = match label.parse::<Labels>() {
+                Ok(label) => {
+                    let (instance, build_id) = split_build_ref(&build_id);
+                    let label = HumanLabel {
+                        build_id: build_id.to_string(),
+                        instance: instance.unwrap_or_default().to_string(),
+                        label,
+                        author: author.or_else(|| std::env::var("USER").ok()),
+                        timestamp: String::new(),
+                        note,
+                        issue,
+                    };
+                    save_label(&data_path, label).await
+                }
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(label) => println!(
+                    "✅\tLabelled build {}/{} as {}",
+                    label.instance,
+                    label.build_id,
+                    label.label.to_str()
+                ),
+                Err(e) => println!("⛔\t\x1b[93m\x1b[1mLabelling failed: {}\x1b[0m", e),
+            }
+        }
+        Some(Command::Train) => match train_from_labels(&data_path, &config).await {
+            Ok(classifier) => {
+                let builds: usize = classifier
+                    .labels
+                    .values()
+                    .map(|statistics| statistics.builds)
+                    .sum();
+                println!("✅\tTrained the classifier on {} labelled builds", builds)
+            }
+            Err(e) => println!("⛔\t\x1b[93m\x1b[1mTraining failed: {}\x1b[0m", e),
+        },
+        Some(Command::Flakes { days }) => {
+            let days = days.unwrap_or(config.analysis.flakes.window_days);
+            match flake_report(&data_path, days).await {
+                Ok(report) if report.jobs.is_empty() => {
+                    println!("✅\tNo flaky jobs or tests in the last {} days", days)
+                }
+                Ok(report) => {
+                    for job in report.jobs {
+                        println!(
+                            "🎲\t\x1b[1m{}\x1b[0m on {}: {:.0}% of {} commits flaky ({} builds)",
+                            job.job,
+                            job.instance,
+                            job.flakiness * 100.0,
+                            job.commits,
+                            job.builds
+                        );
+                        for commit in job.flaky_commits {
+                            println!(
+                                "\t{}: {} passed, {} failed ({})",
+                                commit.commit,
+                                commit.successes,
+                                commit.failures,
+                                commit.builds.join(", ")
+                            );
+                        }
+                        for test in job.flaky_tests {
+                            println!(
+                                "\t🧪 {}: {} passed, {} failed on {} commits (failed in {})",
+                                test.name,
+                                test.passes,
+                                test.failures,
+                                test.commits,
+                                test.builds.join(", ")
+                            );
+                        }
+                    }
+                }
+                Err(e) => println!("⛔\t\x1b[93m\x1b[1mFlake detection failed: {}\x1b[0m", e),
+            }
+        }
+        Some(Command::Builds { state, days }) => {
+            match list_builds(&data_path, days, &parse_states(state.as_deref())).await {
+                Ok(builds) if builds.is_empty() => println!("✅\tNo matching builds collected"),
+                Ok(builds) => {
+                    for build in builds {
+                        println!(
+                            "{}/{}\t{}\t\x1b[1m{}\x1b[0m\t{}",
+                            build.instance,
+                            build.build_id,
+                            build.start_time.unwrap_or_default(),
+                            build.state,
+                            build.job
+                        );
+                    }
+                }
+                Err(e) => println!("⛔\t\x1b[93m\x1b[1mListing builds failed: {}\x1b[0m", e),
+            }
+        }
+        Some(Command::Verify { build_id }) => {
+            let build_refs = match &build_id {
+                Some(build_id) => {
+                    let (instance, build_id) = split_build_ref(build_id);
+                    Ok(vec![(instance.map(str::to_string), build_id.to_string())])
+                }
+                None => downloaded_builds(&data_path).await.map(|builds| {
+                    builds
+                        .into_iter()
+                        .map(|(instance, build_id)| (Some(instance), build_id))
+                        .collect()
+                }),
+            };
+            match build_refs {
+                Ok(build_refs) => {
+                    for (instance, build_id) in build_refs {
+                        let build =
+                            match find_build(&build_id, instance.as_deref(), &data_path).await {
+                                Ok(Some(build)) => build,
+                                Ok(None) => {
+                                    println!(
+                                    "⛔\t\x1b[93m\x1b[1mBuild {} has not been collected yet\x1b[0m",
+                                    build_id
+                                );
+                                    continue;
+                                }
+                                Err(e) => {
+                                    println!(
+                                    "⛔\t\x1b[93m\x1b[1mVerification of build {} failed: {}\x1b[0m",
+                                    build_id, e
+                                );
+                                    continue;
+                                }
+                            };
+                        match verify_artifacts(&build, &data_path).await {
+                            Ok(summary) => println!(
+                                "✅\tVerified build {}/{}: {} files intact, {} unverified, {} downloaded of which {} were corrupt or incomplete",
+                                build.instance,
+                                build.build_id,
+                                summary.existing - summary.unverified,
+                                summary.unverified,
+                                summary.downloaded,
+                                summary.corrupt
+                            ),
+                            Err(e) => println!(
+                                "⛔\t\x1b[93m\x1b[1mVerification of build {}/{} failed: {}\x1b[0m",
+                                build.instance, build.build_id, e
+                            ),
+                        }
+                    }
+                }
+                Err(e) => println!("⛔\t\x1b[93m\x1b[1mVerification failed: {}\x1b[0m", e),
+            }
+        }
+        None => {}
+    }
+
     if http_server {
-        let data_path_for_server = data_path.clone();
-        let build_info_call =
-            move |build_info: Query<BuildId>| handler_api_build(build_info, data_path_for_server);
+        let state = Arc::new(AppState { data_path, config });
         let app = Router::new()
             .route("/", get(handler))
-            .route("/api/build", get(build_info_call))
+            .route("/api/build", get(handler_api_build))
+            .route("/api/builds", get(handler_api_builds))
             .route("/api/compare", get(handler_api_compare))
-            .route("/build/:build_id", get(handler_build_id));
-        let app = app.fallback(get(handler_404));
+            .route("/api/build/:build_id/label", post(handler_api_label))
+            .route("/api/flakes", get(handler_api_flakes))
+            .route("/build/:build_id", get(handler_build_id))
+            .fallback(get(handler_404))
+            .with_state(state);
 
         axum::Server::bind(&"0.0.0.0:8080".parse().unwrap())
             .serve(app.into_make_service())
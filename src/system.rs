@@ -1,3 +1,9 @@
+use crate::error::{Error, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
 use walkdir::WalkDir;
 
 /// Provide timestamp as dash-separated String to be used in identifiers
@@ -20,22 +26,17 @@ pub fn add_time_id() -> String {
 /// # Returns
 ///
 /// Vector of Strings containing the paths to all first-level files in the given folder
-pub async fn files_in_folder(path: &str) -> Vec<String> {
+pub async fn files_in_folder(path: &str) -> Result<Vec<String>> {
     let mut files = Vec::new();
-    for entry in std::fs::read_dir(&path).expect(
-        format!(
-            "The data has not been gathered to and cannot be read from {}",
-            &path
-        )
-        .as_str(),
-    ) {
-        let entry = entry.expect("Failed to get entry");
+    let entries = std::fs::read_dir(path).map_err(|e| Error::io(path, e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| Error::io(path, e))?;
         let path = entry.path();
         if path.is_file() {
-            files.push(path.to_str().unwrap().to_string());
+            files.push(path.to_string_lossy().to_string());
         }
     }
-    files
+    Ok(files)
 }
 
 /// Recursively list all files in a folder using absolute paths
@@ -52,9 +53,9 @@ pub async fn create_file_index(root_path: String) -> Vec<String> {
         .into_iter()
         .filter_map(|e| e.ok())
         .filter(|e| e.file_type().is_file())
-        .map(|e| e.path().to_str().unwrap().to_string())
+        .map(|e| e.path().to_string_lossy().to_string())
         .collect::<Vec<String>>();
-    return file_list;
+    file_list
 }
 
 /// Add trailing slash to a given path if it does not end with one
@@ -68,12 +69,66 @@ pub async fn create_file_index(root_path: String) -> Vec<String> {
 /// String with trailing slash
 pub fn check_slash(path: &str) -> String {
     if path.ends_with("/") {
-        return path.to_string();
+        path.to_string()
     } else {
-        return format!("{}/", path);
+        format!("{}/", path)
     }
 }
 
+/// Create a directory and all of its parents if it does not exist yet
+///
+/// # Arguments
+///
+/// * `path` - Path of the directory
+pub fn create_dir(path: &str) -> Result<()> {
+    if !Path::new(path).exists() {
+        std::fs::create_dir_all(path).map_err(|e| Error::io(path, e))?;
+    }
+    Ok(())
+}
+
+/// Write a file atomically, so that it either keeps its previous content or has the new one
+///
+/// The content is written to `<path>.part` first, which is renamed to `path` once it is complete.
+///
+/// # Arguments
+///
+/// * `path` - Path of the file to be created or overwritten
+/// * `content` - The content to be written
+pub fn write_atomic(path: &str, content: &[u8]) -> Result<()> {
+    let part_path = format!("{}.part", path);
+    let mut file = File::create(&part_path).map_err(|e| Error::io(&part_path, e))?;
+    file.write_all(content)
+        .and_then(|_| file.sync_all())
+        .map_err(|e| Error::io(&part_path, e))?;
+    std::fs::rename(&part_path, path).map_err(|e| Error::io(path, e))
+}
+
+/// Serialize a value as pretty-printed JSON into a file, replacing it atomically
+///
+/// # Arguments
+///
+/// * `path` - Path of the file to be created or overwritten
+/// * `value` - The value to be written
+pub fn write_json<T: Serialize + ?Sized>(path: &str, value: &T) -> Result<()> {
+    let content = serde_json::to_vec_pretty(value).map_err(|e| Error::parse(path, e))?;
+    write_atomic(path, &content)
+}
+
+/// Deserialize a JSON file
+///
+/// # Arguments
+///
+/// * `path` - Path of the file to be read
+///
+/// # Returns
+///
+/// The deserialized contents of the file
+pub fn read_json<T: DeserializeOwned>(path: &str) -> Result<T> {
+    let file = File::open(path).map_err(|e| Error::io(path, e))?;
+    serde_json::from_reader(std::io::BufReader::new(file)).map_err(|e| Error::parse(path, e))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -90,4 +145,23 @@ mod tests {
         let result = check_slash("path");
         assert!(result == "path/");
     }
+
+    #[test]
+    /// Checks that reading a missing file returns an IO error instead of panicking
+    fn test_read_json_missing_file() {
+        let result: Result<Vec<String>> = read_json("does/not/exist.json");
+        assert!(matches!(result, Err(Error::Io { .. })));
+    }
+
+    #[test]
+    /// Checks that write_atomic replaces the file and does not leave a partial file behind
+    fn test_write_atomic() {
+        let path = std::env::temp_dir().join(format!("arcalog-atomic-{}", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+        write_atomic(&path, b"first").unwrap();
+        write_atomic(&path, b"second").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"second");
+        assert!(!Path::new(&format!("{}.part", path)).exists());
+        std::fs::remove_file(&path).unwrap();
+    }
 }